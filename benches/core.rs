@@ -0,0 +1,182 @@
+//! Criterion benchmarks for the BK-tree and coverage tracker hot paths.
+//!
+//! Run with `cargo bench --bench core`. Every input is generated from a fixed
+//! seed, so numbers are comparable across commits on the same machine.
+//!
+//! The `trace` inputs replay a captured hash stream when `GAMECOV_BENCH_TRACE`
+//! names a file with one hash per line (decimal or `0x` hex); otherwise they
+//! use a synthetic gameplay-like stream (consecutive frames a few bits apart,
+//! with occasional scene cuts).
+
+use std::collections::HashSet;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use gamecov_core::bktree::BKTreeInner;
+use gamecov_core::monitor::CoverageTrackerInner;
+use gamecov_core::rng::SplitMix64;
+
+const SEED: u64 = 42;
+const N: usize = 10_000;
+const QUERIES: usize = 1_000;
+/// Default clustering radius of the Python monitors (`gamecov.env.RADIUS`).
+const RADIUS: u32 = 10;
+/// Frames per recording, matching `benchmarks/conftest.py`.
+const FRAMES_PER_RECORDING: usize = 20;
+
+/// Independent uniform 64-bit hashes.
+fn uniform(n: usize, seed: u64) -> Vec<u64> {
+    let mut rng = SplitMix64::new(seed);
+    (0..n).map(|_| rng.next_u64()).collect()
+}
+
+/// Hashes scattered around `centers` random centres, each at most `spread` bit flips away.
+fn clustered(n: usize, centers: usize, spread: u32, seed: u64) -> Vec<u64> {
+    let mut rng = SplitMix64::new(seed);
+    let roots: Vec<u64> = (0..centers).map(|_| rng.next_u64()).collect();
+    (0..n)
+        .map(|_| {
+            let mut x = roots[rng.below(centers as u64) as usize];
+            for _ in 0..rng.below(u64::from(spread) + 1) {
+                x ^= 1 << rng.below(64);
+            }
+            x
+        })
+        .collect()
+}
+
+/// Captured trace from `GAMECOV_BENCH_TRACE`, or a synthetic gameplay walk.
+fn trace(n: usize, seed: u64) -> Vec<u64> {
+    if let Ok(path) = std::env::var("GAMECOV_BENCH_TRACE") {
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {path}: {e}"));
+        return text
+            .split_whitespace()
+            .map(|tok| match tok.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => tok.parse(),
+            })
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| panic!("bad hash in {path}: {e}"));
+    }
+    let mut rng = SplitMix64::new(seed);
+    let mut x = rng.next_u64();
+    (0..n)
+        .map(|_| {
+            if rng.below(200) == 0 {
+                x = rng.next_u64(); // scene cut
+            } else {
+                for _ in 0..rng.below(3) {
+                    x ^= 1 << rng.below(64);
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+fn inputs() -> [(&'static str, Vec<u64>); 3] {
+    [
+        ("uniform", uniform(N, SEED)),
+        ("clustered", clustered(N, 100, 8, SEED)),
+        ("trace", trace(N, SEED)),
+    ]
+}
+
+fn build_tree(xs: &[u64]) -> BKTreeInner {
+    let mut tree = BKTreeInner::with_capacity(xs.len());
+    for &x in xs {
+        tree.add(x);
+    }
+    tree
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bktree_insert");
+    for (name, xs) in inputs() {
+        group.throughput(Throughput::Elements(xs.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &xs, |b, xs| {
+            b.iter(|| build_tree(black_box(xs)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_find_all_within(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bktree_find_all_within");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+    for (name, xs) in inputs() {
+        let tree = build_tree(&xs);
+        // half stored hashes, half fresh ones
+        let step = (xs.len() / (QUERIES / 2)).max(1);
+        let mut queries: Vec<u64> = xs.iter().step_by(step).copied().take(QUERIES / 2).collect();
+        queries.extend(uniform(QUERIES - queries.len(), SEED + 1));
+        for radius in [4, RADIUS] {
+            group.bench_with_input(BenchmarkId::new(name, radius), &queries, |b, queries| {
+                b.iter(|| {
+                    queries
+                        .iter()
+                        .map(|&q| tree.find_all_within(black_box(q), radius).len())
+                        .sum::<usize>()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_add_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tracker_add_hash");
+    for (name, xs) in inputs() {
+        group.throughput(Throughput::Elements(xs.len() as u64));
+        for radius in [1, RADIUS] {
+            group.bench_with_input(BenchmarkId::new(name, radius), &xs, |b, xs| {
+                b.iter(|| {
+                    let mut tracker = CoverageTrackerInner::new(radius);
+                    for &x in xs {
+                        tracker.add_hash(black_box(x));
+                    }
+                    tracker.coverage_count()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Mirrors `RustBKFrameMonitor.add_cov` as driven by the Python benchmarks:
+/// recordings of 20 frames each, exact duplicates skipped by a caller-side
+/// set before `add_hash`, and `coverage_count` read after every recording.
+fn bench_monitor_hot_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("monitor_hot_path");
+    for (name, xs) in inputs() {
+        group.throughput(Throughput::Elements(xs.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &xs, |b, xs| {
+            b.iter_batched(
+                || (CoverageTrackerInner::new(RADIUS), HashSet::new()),
+                |(mut tracker, mut seen)| {
+                    let mut counts = 0;
+                    for recording in xs.chunks(FRAMES_PER_RECORDING) {
+                        for &x in recording {
+                            if seen.insert(x) {
+                                tracker.add_hash(x);
+                            }
+                        }
+                        counts += tracker.coverage_count();
+                    }
+                    counts
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_insert,
+    bench_find_all_within,
+    bench_add_hash,
+    bench_monitor_hot_path
+);
+criterion_main!(benches);
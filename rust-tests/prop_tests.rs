@@ -1,6 +1,8 @@
-use gamecov_core::bktree::{hamming, BKTreeInner};
+use gamecov_core::bktree::{hamming, neighbors_within_bitflips, BKTreeInner};
+use gamecov_core::distance::{Distance, MaskedHamming, WeightedHamming};
 use gamecov_core::monitor::CoverageTrackerInner;
-use gamecov_core::unionfind::UnionFindInner;
+use gamecov_core::sharded::ShardedCoverageTrackerInner;
+use gamecov_core::unionfind::{UnionFindInner, UnionStrategy};
 use proptest::prelude::*;
 
 // ── BK-tree properties ───────────────────────────────────────────────────
@@ -79,6 +81,181 @@ proptest! {
 
         assert_eq!(got, expected, "BK-tree must return exactly the brute-force results");
     }
+
+    #[test]
+    fn bktree_fused_insert_matches_query_then_add(
+        values in prop::collection::vec(0u64..1024, 1..60),
+        radius in 0u32..6,
+    ) {
+        let mut fused = BKTreeInner::new();
+        let mut plain = BKTreeInner::new();
+        for &v in &values {
+            let mut expected = plain.find_all_within(v, radius);
+            expected.retain(|&x| x != v);
+            expected.sort();
+            let (inserted, mut got) = fused.add_and_find_within(v, radius);
+            got.sort();
+            assert_eq!(inserted, plain.add(v));
+            assert_eq!(got, expected);
+        }
+        assert_eq!(fused.debug_check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn bktree_never_stores_duplicates(
+        ops in prop::collection::vec((0u64..64, any::<bool>()), 1..120),
+    ) {
+        // Small value range forces repeated inserts, removals and revivals
+        let mut tree = BKTreeInner::new();
+        for &(v, insert) in &ops {
+            if insert {
+                tree.add(v);
+            } else {
+                tree.remove(v);
+            }
+            assert!(!tree.has_duplicates());
+        }
+    }
+
+    #[test]
+    fn bktree_masked_completeness(
+        values in prop::collection::vec(any::<u64>(), 1..30),
+        query in any::<u64>(),
+        mask in any::<u64>(),
+        radius in 0u32..10,
+    ) {
+        let metric = MaskedHamming { mask };
+        let mut tree = BKTreeInner::with_metric(metric);
+        for &v in &values {
+            tree.add(v);
+        }
+        let mut expected: Vec<u64> = values.iter()
+            .copied()
+            .filter(|&v| metric.distance(query, v) <= radius)
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        let mut got = tree.find_all_within(query, radius);
+        got.sort();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn bktree_weighted_completeness(
+        values in prop::collection::vec(any::<u64>(), 1..40),
+        query in any::<u64>(),
+        weights in prop::collection::vec(0u32..16, 64),
+        radius in 0u32..60,
+    ) {
+        // pruning never drops a true match under any weighting
+        let metric = WeightedHamming { weights: weights.try_into().unwrap() };
+        let mut tree = BKTreeInner::with_metric(metric);
+        for &v in &values {
+            tree.add(v);
+        }
+        let mut expected: Vec<u64> = values.iter()
+            .copied()
+            .filter(|&v| metric.distance(query, v) <= radius)
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        let mut got = tree.find_all_within(query, radius);
+        got.sort();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn bktree_compact_preserves_queries(
+        values in prop::collection::vec(any::<u64>(), 1..40),
+        removals in prop::collection::vec(any::<prop::sample::Index>(), 0..20),
+        query in any::<u64>(),
+        radius in 0u32..40,
+    ) {
+        let mut tree = BKTreeInner::new();
+        for &v in &values {
+            tree.add(v);
+        }
+        for idx in &removals {
+            tree.remove(values[idx.index(values.len())]);
+        }
+        let mut before = tree.find_all_within(query, radius);
+        before.sort();
+        let len_before = tree.len();
+
+        assert_eq!(tree.debug_check_invariants(), Ok(()));
+        tree.compact();
+        assert_eq!(tree.debug_check_invariants(), Ok(()));
+        let mut after = tree.find_all_within(query, radius);
+        after.sort();
+        assert_eq!(before, after);
+        assert_eq!(tree.len(), len_before);
+        assert_eq!(tree.capacity_nodes(), tree.live_nodes());
+    }
+
+    #[test]
+    fn bktree_matches_bitflip_enumeration(
+        values in prop::collection::vec(any::<u64>(), 1..30),
+        query in any::<u64>(),
+        radius in 0u32..3,
+    ) {
+        // Seed neighbours of the query so small radii actually produce matches
+        let mut tree = BKTreeInner::new();
+        let mut stored = std::collections::HashSet::new();
+        for (i, &v) in values.iter().enumerate() {
+            let v = if i % 2 == 0 { query ^ (v & (v >> 7) & (v >> 13)) } else { v };
+            tree.add(v);
+            stored.insert(v);
+        }
+
+        let mut expected: Vec<u64> = neighbors_within_bitflips(query, radius)
+            .filter(|y| stored.contains(y))
+            .collect();
+        expected.sort();
+
+        let mut got = tree.find_all_within(query, radius);
+        got.sort();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn bktree_merge_is_set_union(
+        a in prop::collection::vec(0u64..500, 0..60),
+        b in prop::collection::vec(0u64..500, 0..60),
+    ) {
+        let (mut ta, mut tb) = (BKTreeInner::new(), BKTreeInner::new());
+        for &x in &a {
+            ta.add(x);
+        }
+        for &x in &b {
+            tb.add(x);
+        }
+        let merged = ta.merge(tb);
+        let mut expected: Vec<u64> = a.iter().chain(&b).copied().collect();
+        expected.sort_unstable();
+        expected.dedup();
+        let mut got: Vec<u64> = merged.values().collect();
+        got.sort_unstable();
+        assert_eq!(got, expected);
+        assert_eq!(merged.debug_check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn bktree_nearest_matches_brute_force(
+        values in prop::collection::vec(any::<u64>(), 0..60),
+        query in any::<u64>(),
+    ) {
+        let mut tree = BKTreeInner::new();
+        for &v in &values {
+            tree.add(v);
+        }
+        let best = values.iter().map(|&v| hamming(query, v)).min();
+        assert_eq!(tree.nearest(query).map(|(_, d)| d), best);
+        if let Some((v, d)) = tree.nearest(query) {
+            assert_eq!(hamming(query, v), d);
+        }
+    }
 }
 
 // ── UnionFind properties ─────────────────────────────────────────────────
@@ -101,9 +278,10 @@ proptest! {
             for (ia, ib) in &unions {
                 let a = deduped[ia.index(deduped.len())];
                 let b = deduped[ib.index(deduped.len())];
-                uf.union(a, b);
+                uf.union(a, b).unwrap();
             }
         }
+        assert_eq!(uf.debug_check_invariants(), Ok(()));
         assert!(uf.component_count() <= deduped.len());
         if !deduped.is_empty() {
             assert!(uf.component_count() >= 1);
@@ -116,17 +294,62 @@ proptest! {
         let mut uf1 = UnionFindInner::new();
         uf1.make_set(a);
         uf1.make_set(b);
-        uf1.union(a, b);
+        uf1.union(a, b).unwrap();
 
         let mut uf2 = UnionFindInner::new();
         uf2.make_set(a);
         uf2.make_set(b);
-        uf2.union(b, a);
+        uf2.union(b, a).unwrap();
 
-        assert_eq!(uf1.find(a), uf1.find(b));
-        assert_eq!(uf2.find(a), uf2.find(b));
+        assert_eq!(uf1.find(a).unwrap(), uf1.find(b).unwrap());
+        assert_eq!(uf2.find(a).unwrap(), uf2.find(b).unwrap());
         assert_eq!(uf1.component_count(), uf2.component_count());
     }
+
+    #[test]
+    fn uf_deterministic_roots_order_independent(
+        edges in prop::collection::vec((0u64..20, 0u64..20), 0..40),
+    ) {
+        let build = |edges: &[(u64, u64)]| {
+            let mut uf = UnionFindInner::with_strategy(UnionStrategy::DeterministicRoots);
+            for x in 0..20 {
+                uf.make_set(x);
+            }
+            for &(a, b) in edges {
+                uf.union(a, b).unwrap();
+            }
+            uf
+        };
+        let mut reversed = edges.clone();
+        reversed.reverse();
+        let mut uf1 = build(&edges);
+        let mut uf2 = build(&reversed);
+        for x in 0..20 {
+            assert_eq!(uf1.find(x).unwrap(), uf2.find(x).unwrap());
+        }
+    }
+
+    #[test]
+    fn uf_find_stable_between_unions(
+        edges in prop::collection::vec((0u64..30, 0u64..30), 0..60),
+        queries in prop::collection::vec(0u64..30, 0..100),
+    ) {
+        let mut uf = UnionFindInner::new();
+        for x in 0..30 {
+            uf.make_set(x);
+        }
+        for &(a, b) in &edges {
+            uf.union(a, b).unwrap();
+        }
+        let roots: Vec<u64> = (0..30).map(|x| uf.root_of(x).unwrap()).collect();
+        // path splitting along arbitrary query orders must not move any root
+        for &q in &queries {
+            assert_eq!(uf.find(q).unwrap(), roots[q as usize]);
+        }
+        for x in 0..30 {
+            assert_eq!(uf.find(x).unwrap(), roots[x as usize]);
+        }
+    }
 }
 
 // ── CoverageTracker properties ───────────────────────────────────────────
@@ -159,6 +382,59 @@ proptest! {
             tracker.add_hash(h);
         }
         assert!(tracker.coverage_count() <= tracker.total_unique());
+        assert_eq!(tracker.debug_check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn tracker_radius_zero_counts_unique(
+        hashes in prop::collection::vec(any::<u64>(), 1..100),
+    ) {
+        let mut tracker = CoverageTrackerInner::new(0);
+        for &h in &hashes {
+            tracker.add_hash(h);
+            assert_eq!(tracker.coverage_count(), tracker.total_unique());
+        }
+    }
+
+    #[test]
+    fn tracker_radius_one_fast_path_matches_tree(
+        seeds in prop::collection::vec(any::<u64>(), 1..20),
+        flips in prop::collection::vec((any::<prop::sample::Index>(), 0u32..64), 0..60),
+    ) {
+        // Walk single-bit flips away from the seeds so radius-1 edges are common.
+        // MaskedHamming with an empty mask is plain Hamming without the fast path.
+        let mut fast = CoverageTrackerInner::new(1);
+        let mut general = CoverageTrackerInner::with_metric(1, MaskedHamming { mask: 0 });
+        let mut stream = seeds.clone();
+        for (idx, bit) in &flips {
+            stream.push(stream[idx.index(stream.len())] ^ (1u64 << bit));
+        }
+        for &h in &stream {
+            assert_eq!(fast.add_hash_neighbors(h), general.add_hash_neighbors(h));
+            assert_eq!(fast.coverage_count(), general.coverage_count());
+        }
+        assert_eq!(fast.total_unique(), general.total_unique());
+    }
+
+    #[test]
+    fn tracker_matches_naive_union(
+        hashes in prop::collection::vec(0u64..256, 1..80),
+        radius in 1u32..4,
+    ) {
+        // Reference: union the new hash with every neighbour, one call each
+        let mut tracker = CoverageTrackerInner::new(radius);
+        let mut tree = BKTreeInner::new();
+        let mut uf = UnionFindInner::new();
+        for &h in &hashes {
+            tracker.add_hash(h);
+            if tree.add(h) {
+                uf.make_set(h);
+                for nb in tree.find_all_within(h, radius) {
+                    uf.union(h, nb).unwrap();
+                }
+            }
+            assert_eq!(tracker.coverage_count(), uf.component_count());
+        }
     }
 
     #[test]
@@ -174,3 +450,23 @@ proptest! {
         assert_eq!(tracker.total_unique(), 0);
     }
 }
+
+// ── ShardedCoverageTracker properties ────────────────────────────────────
+
+proptest! {
+    #[test]
+    fn sharded_matches_unsharded_unique(
+        hashes in prop::collection::vec(any::<u64>(), 1..100),
+        radius in 0u32..10,
+        num_shards in 1usize..16,
+    ) {
+        let sharded = ShardedCoverageTrackerInner::new(radius, num_shards);
+        let mut single = CoverageTrackerInner::new(radius);
+        for &h in &hashes {
+            assert_eq!(sharded.add_hash(h), single.add_hash(h));
+        }
+        assert_eq!(sharded.total_unique(), single.total_unique());
+        // clusters never span shards, so sharding can only split components
+        assert!(sharded.coverage_count() >= single.coverage_count());
+    }
+}
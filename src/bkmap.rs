@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::bktree::distance_band;
+use crate::distance::{Distance, Hamming};
+
+struct BKMapNode<V> {
+    key: u64,
+    value: V,
+    children: HashMap<u32, usize>,
+}
+
+/// BK-tree keyed by u64 hashes that carries a payload per key.
+///
+/// Same arena layout and pruning as `BKTreeInner`; each node additionally
+/// stores a value such as a frame id or file offset, which neighbour queries
+/// return alongside the matching hash.
+pub struct BKMap<V, D: Distance = Hamming> {
+    nodes: Vec<BKMapNode<V>>,
+    metric: D,
+}
+
+impl<V> BKMap<V> {
+    pub fn new() -> Self {
+        Self::with_metric(Hamming)
+    }
+}
+
+impl<V, D: Distance + Default> Default for BKMap<V, D> {
+    fn default() -> Self {
+        Self::with_metric(D::default())
+    }
+}
+
+impl<V, D: Distance> BKMap<V, D> {
+    pub fn with_metric(metric: D) -> Self {
+        Self {
+            nodes: Vec::new(),
+            metric,
+        }
+    }
+
+    /// Insert `x` with payload `value`. Returns false, keeping the stored
+    /// payload, if `x` is already present.
+    pub fn add(&mut self, x: u64, value: V) -> bool {
+        if self.nodes.is_empty() {
+            self.nodes.push(BKMapNode {
+                key: x,
+                value,
+                children: HashMap::new(),
+            });
+            return true;
+        }
+
+        let mut idx = 0;
+        loop {
+            if self.nodes[idx].key == x {
+                return false; // exact duplicate
+            }
+            let d = self.metric.distance(x, self.nodes[idx].key);
+            if let Some(&child_idx) = self.nodes[idx].children.get(&d) {
+                idx = child_idx;
+            } else {
+                let new_idx = self.nodes.len();
+                self.nodes.push(BKMapNode {
+                    key: x,
+                    value,
+                    children: HashMap::new(),
+                });
+                self.nodes[idx].children.insert(d, new_idx);
+                return true;
+            }
+        }
+    }
+
+    /// Payload stored for exactly `x`.
+    pub fn get(&self, x: u64) -> Option<&V> {
+        self.locate(x).map(|idx| &self.nodes[idx].value)
+    }
+
+    /// Mutable payload stored for exactly `x`.
+    pub fn get_mut(&mut self, x: u64) -> Option<&mut V> {
+        self.locate(x).map(|idx| &mut self.nodes[idx].value)
+    }
+
+    /// Return every `(hash, payload)` within distance `radius` of `x`.
+    pub fn find_all_within(&self, x: u64, radius: u32) -> Vec<(u64, &V)> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        let mut results = Vec::new();
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.key);
+            if d <= radius {
+                results.push((node.key, &node.value));
+            }
+            let (lo, hi) = distance_band(d, radius);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+        results
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Arena index of the node holding exactly `x`.
+    fn locate(&self, x: u64) -> Option<usize> {
+        let mut idx = 0;
+        while let Some(node) = self.nodes.get(idx) {
+            if node.key == x {
+                return Some(idx);
+            }
+            idx = *node.children.get(&self.metric.distance(x, node.key))?;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_get() {
+        let mut map = BKMap::new();
+        assert!(map.add(0b0000, "a"));
+        assert!(map.add(0b0011, "b"));
+        assert!(!map.add(0b0011, "c")); // duplicate keeps the first payload
+        assert_eq!(map.get(0b0011), Some(&"b"));
+        assert_eq!(map.get(0b0111), None);
+        assert_eq!(map.len(), 2);
+
+        *map.get_mut(0b0000).unwrap() = "z";
+        assert_eq!(map.get(0b0000), Some(&"z"));
+    }
+
+    #[test]
+    fn test_find_all_within_returns_payloads() {
+        let mut map = BKMap::new();
+        for (i, x) in [0b0000u64, 0b0001, 0b0011, 0b1111_0000].into_iter().enumerate() {
+            map.add(x, i);
+        }
+        let mut found = map.find_all_within(0b0000, 1);
+        found.sort();
+        assert_eq!(found, vec![(0b0000, &0), (0b0001, &1)]);
+        assert!(BKMap::<()>::new().find_all_within(0, 64).is_empty());
+    }
+}
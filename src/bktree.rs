@@ -1,61 +1,496 @@
 use std::collections::HashMap;
 
+use crate::distance::{Distance, Hamming};
+use crate::error::GamecovError;
+use crate::frozen::FrozenCompactTree;
+use crate::persist::{self, Kind};
+use crate::rng::SplitMix64;
+
 /// A node in the BK-tree arena.
+#[derive(Clone)]
 struct BKNode {
     val: u64,
     children: HashMap<u32, usize>,
+    /// Tombstone left by `remove`; the node still routes traversals.
+    deleted: bool,
+}
+
+impl BKNode {
+    fn new(val: u64) -> Self {
+        Self {
+            val,
+            children: HashMap::new(),
+            deleted: false,
+        }
+    }
 }
 
 /// BK-tree for Hamming-distance nearest-neighbour queries on u64 hashes.
 ///
 /// Nodes are stored in a flat Vec (arena allocation) for cache friendliness.
-pub struct BKTreeInner {
+/// Removed values stay in the arena as tombstones until `compact` is called.
+/// The metric defaults to plain Hamming; see [`Distance`] for alternatives.
+#[derive(Clone)]
+pub struct BKTreeInner<D: Distance = Hamming> {
     nodes: Vec<BKNode>,
+    live: usize,
+    metric: D,
+    /// Capacity reserved for a node's children map when it gains its first child.
+    child_capacity: usize,
+    /// Fixed number of slots to add when the arena is full; 0 uses Vec doubling.
+    growth_chunk: usize,
+    /// Largest radius `check_radius` accepts, if capped.
+    max_radius: Option<u32>,
+    /// Bytes allocated by all children maps, kept current so `memory_bytes` is O(1).
+    child_bytes: usize,
+}
+
+/// Order of the values returned by [`BKTreeInner::find_all_within_ordered`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// Traversal order; no sorting cost.
+    #[default]
+    Unsorted,
+    /// Arena order, which is insertion order unless `optimize_layout` has
+    /// renumbered the nodes. A value revived after `remove` keeps its slot.
+    Insertion,
+    /// Ascending by value.
+    ValueAscending,
+    /// Ascending by distance to the query, ties by value.
+    DistanceAscending,
 }
 
+impl std::str::FromStr for ResultOrder {
+    type Err = GamecovError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unsorted" => Ok(Self::Unsorted),
+            "insertion" => Ok(Self::Insertion),
+            "value" => Ok(Self::ValueAscending),
+            "distance" => Ok(Self::DistanceAscending),
+            _ => Err(GamecovError::InvalidArgument(format!(
+                "unknown result order {s:?}; expected \"unsorted\", \"insertion\", \"value\" or \"distance\""
+            ))),
+        }
+    }
+}
+
+/// Shape statistics of a BK-tree arena, tombstones included.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    /// Arena slots, live or tombstoned.
+    pub nodes: usize,
+    /// Nodes without children.
+    pub leaves: usize,
+    /// Depth of the deepest node; the root has depth 0.
+    pub max_depth: usize,
+    /// Mean node depth.
+    pub mean_depth: f64,
+    /// Mean number of children over nodes that have any.
+    pub mean_branching: f64,
+}
+
+/// Verdict from [`BKTreeInner::health`] on whether the tree still prunes well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeHealth {
+    /// Too few nodes for the shape statistics to mean anything.
+    TooSmall,
+    Healthy,
+    /// The tree has degenerated towards a chain, so queries visit most nodes.
+    Degenerate,
+}
+
+/// Node count below which `health` reports `TooSmall`.
+pub const HEALTH_MIN_NODES: usize = 32;
+
+/// Hamming distance between two 64-bit hashes. Usable in const contexts.
 #[inline(always)]
-pub fn hamming(a: u64, b: u64) -> u32 {
+pub const fn hamming(a: u64, b: u64) -> u32 {
     (a ^ b).count_ones()
 }
 
-impl Default for BKTreeInner {
+/// Child-edge keys `[lo, hi]` that can lead to values within `radius` of a
+/// query at distance `d` from a node, by the triangle inequality.
+///
+/// Both ends saturate, so huge radii never wrap around and prune wrongly.
+#[inline]
+pub const fn distance_band(d: u32, radius: u32) -> (u32, u32) {
+    (d.saturating_sub(radius), d.saturating_add(radius))
+}
+
+/// Full symmetric matrix of pairwise Hamming distances, row `i` holding the
+/// distances from `xs[i]` to every element. O(n^2) time and memory.
+pub fn pairwise_hamming(xs: &[u64]) -> Vec<Vec<u32>> {
+    xs.iter()
+        .map(|&a| xs.iter().map(|&b| hamming(a, b)).collect())
+        .collect()
+}
+
+/// Enumerate every hash within `radius` bit flips of `x`, including `x` itself.
+///
+/// Yields sum(C(64, k)) for k in 0..=radius values, which grows combinatorially:
+/// 2,081 at radius 2 and 43,745 at radius 3. Only use for radius <= 3, e.g. to
+/// build exact neighbour sets when validating tree queries in tests.
+pub fn neighbors_within_bitflips(x: u64, radius: u32) -> impl Iterator<Item = u64> {
+    BitFlips {
+        x,
+        k: 0,
+        max_k: radius.min(64),
+        mask: Some(0),
+    }
+}
+
+/// Iterator over all `k`-bit masks for increasing `k` (Gosper's hack).
+struct BitFlips {
+    x: u64,
+    k: u32,
+    max_k: u32,
+    mask: Option<u64>,
+}
+
+impl Iterator for BitFlips {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let mask = self.mask?;
+        self.mask = if mask == 0 {
+            None
+        } else {
+            // next mask with the same popcount; overflow means this k is exhausted
+            let c = mask & mask.wrapping_neg();
+            mask.checked_add(c).map(|r| (((r ^ mask) >> 2) / c) | r)
+        };
+        if self.mask.is_none() && self.k < self.max_k {
+            self.k += 1;
+            self.mask = Some(if self.k == 64 { u64::MAX } else { (1u64 << self.k) - 1 });
+        }
+        Some(self.x ^ mask)
+    }
+}
+
+impl<D: Distance + Default> Default for BKTreeInner<D> {
     fn default() -> Self {
-        Self::new()
+        Self::with_metric(D::default())
     }
 }
 
 impl BKTreeInner {
     pub fn new() -> Self {
-        Self { nodes: Vec::new() }
+        Self::with_metric(Hamming)
+    }
+
+    /// Build a tree from `xs` inserted in a seeded random order.
+    ///
+    /// A BK-tree's shape depends only on insertion order, and ordered input
+    /// (e.g. sorted hashes or a slowly drifting stream) can produce deep,
+    /// unbalanced trees. Shuffling gives the expected shape of a random order;
+    /// the same `seed` always yields the same tree.
+    pub fn from_slice_balanced(xs: &[u64], seed: u64) -> Self {
+        let mut order = xs.to_vec();
+        let mut rng = SplitMix64::new(seed);
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+        let mut tree = Self::with_capacity(order.len());
+        for x in order {
+            tree.add(x);
+        }
+        tree
+    }
+
+    /// Write the live values in a versioned, checksummed binary format.
+    ///
+    /// Values are stored in arena order, so `load` rebuilds the same tree
+    /// shape (tombstones dropped). Construction settings are not saved.
+    pub fn save<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        let mut payload = Vec::new();
+        persist::put_u64s(&mut payload, self.values().collect::<Vec<_>>().into_iter());
+        persist::write_frame(w, Kind::BKTree, &payload)
+    }
+
+    /// Read a tree written by `save`, rejecting other format versions and corrupt data.
+    pub fn load<R: std::io::Read>(r: R) -> Result<Self, GamecovError> {
+        let values = persist::get_u64s(&persist::read_frame(r, Kind::BKTree)?)?;
+        let mut tree = Self::with_capacity(values.len());
+        for x in values {
+            tree.add(x);
+        }
+        Ok(tree)
+    }
+
+    /// Create an empty tree whose arena holds `capacity` nodes without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut tree = Self::new();
+        tree.reserve(capacity);
+        tree
+    }
+
+    /// Count live values at each Hamming distance `0..=64` from `reference`.
+    ///
+    /// One pass over the arena with no pruning, so it costs n distance
+    /// computations whatever the tree's shape.
+    pub fn distance_histogram_to(&self, reference: u64) -> [usize; 65] {
+        let mut hist = [0; 65];
+        for x in self.values() {
+            hist[hamming(reference, x) as usize] += 1;
+        }
+        hist
+    }
+
+    /// Freeze the tree into a read-only [`FrozenCompactTree`].
+    ///
+    /// Tombstones are dropped and nodes laid out breadth-first (see
+    /// `compact` and `optimize_layout`), then each node's child map is
+    /// flattened into sorted `u8` distance and `u32` index arrays.
+    ///
+    /// # Panics
+    ///
+    /// If the tree holds more than `u32::MAX` values.
+    pub fn into_frozen_compact(mut self) -> FrozenCompactTree {
+        self.compact();
+        self.optimize_layout();
+        let to_u32 = |i: usize| u32::try_from(i).expect("frozen trees hold at most u32::MAX nodes");
+        let mut values = Vec::with_capacity(self.nodes.len());
+        let mut child_start = Vec::with_capacity(self.nodes.len() + 1);
+        let edges = self.nodes.len().saturating_sub(1);
+        let mut child_dist = Vec::with_capacity(edges);
+        let mut child_index = Vec::with_capacity(edges);
+        child_start.push(0);
+        for node in &self.nodes {
+            values.push(node.val);
+            let mut children: Vec<(u32, usize)> = node.children.iter().map(|(&d, &c)| (d, c)).collect();
+            children.sort_unstable();
+            for (d, c) in children {
+                child_dist.push(d as u8); // Hamming distances of u64 are at most 64
+                child_index.push(to_u32(c));
+            }
+            child_start.push(to_u32(child_index.len()));
+        }
+        FrozenCompactTree::from_parts(values, child_start, child_dist, child_index)
+    }
+}
+
+impl<D: Distance> BKTreeInner<D> {
+    pub fn with_metric(metric: D) -> Self {
+        Self {
+            nodes: Vec::new(),
+            live: 0,
+            metric,
+            child_capacity: 0,
+            growth_chunk: 0,
+            max_radius: None,
+            child_bytes: 0,
+        }
+    }
+
+    /// Reserve room for `capacity` children when a node gains its first child.
+    ///
+    /// Leaves never allocate a children map, so this only affects internal
+    /// nodes; it trades a little memory for fewer rehashes while they fill up.
+    pub fn with_child_capacity(mut self, capacity: usize) -> Self {
+        self.child_capacity = capacity;
+        self
     }
 
-    /// Insert a hash value. Returns false if exact duplicate (distance 0).
+    /// Grow the arena by exactly `chunk` slots whenever it fills up.
+    ///
+    /// By default the arena doubles, and while the old buffer is copied both
+    /// are live: a full arena of n nodes briefly needs room for 3n. Fixed
+    /// chunks cap that spike at 2n + chunk, at the cost of more frequent
+    /// (O(n) each) copies. When the final size is known, `with_capacity` or
+    /// `reserve` avoid reallocation entirely. `chunk == 0` restores doubling.
+    pub fn with_growth_chunk(mut self, chunk: usize) -> Self {
+        self.growth_chunk = chunk;
+        self
+    }
+
+    /// Cap the query radius accepted by `check_radius`; `None` removes the cap.
+    ///
+    /// Guards against accidental near-full scans such as `radius = 64`. The
+    /// query methods stay infallible; entry points that take a radius from
+    /// callers (such as the Python bindings) validate it with `check_radius`.
+    pub fn with_max_radius(mut self, max_radius: Option<u32>) -> Self {
+        self.max_radius = max_radius;
+        self
+    }
+
+    pub fn max_radius(&self) -> Option<u32> {
+        self.max_radius
+    }
+
+    /// Reject `radius` if it exceeds the configured `max_radius`.
+    pub fn check_radius(&self, radius: u32) -> Result<(), GamecovError> {
+        match self.max_radius {
+            Some(max) if radius > max => Err(GamecovError::RadiusTooLarge { radius, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reserve arena room for at least `additional` more nodes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve_exact(additional);
+    }
+
+    /// Release unused arena capacity. Tombstones stay; `compact` drops them.
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    pub fn metric(&self) -> &D {
+        &self.metric
+    }
+
+    /// Insert a hash value. Returns false if exact duplicate.
     pub fn add(&mut self, x: u64) -> bool {
+        self.add_or_get(x).is_ok()
+    }
+
+    /// Insert a hash value, or return the stored value on an exact duplicate.
+    ///
+    /// Returns `Ok(())` on insertion and `Err(existing)` when `x` is already
+    /// stored. Reviving a removed value counts as an insertion.
+    pub fn add_or_get(&mut self, x: u64) -> Result<(), u64> {
         if self.nodes.is_empty() {
-            self.nodes.push(BKNode {
-                val: x,
-                children: HashMap::new(),
-            });
-            return true;
+            self.push_node(x);
+            self.live += 1;
+            return Ok(());
         }
 
         let mut idx = 0;
         loop {
-            let d = hamming(x, self.nodes[idx].val);
-            if d == 0 {
-                return false; // exact duplicate
+            let d = self.metric.distance(x, self.nodes[idx].val);
+            if self.nodes[idx].val == x {
+                if self.nodes[idx].deleted {
+                    // revive the tombstone in place
+                    self.nodes[idx].deleted = false;
+                    self.live += 1;
+                    return Ok(());
+                }
+                return Err(self.nodes[idx].val); // exact duplicate
             }
             if let Some(&child_idx) = self.nodes[idx].children.get(&d) {
                 idx = child_idx;
             } else {
-                let new_idx = self.nodes.len();
-                self.nodes.push(BKNode {
-                    val: x,
-                    children: HashMap::new(),
-                });
-                self.nodes[idx].children.insert(d, new_idx);
+                let new_idx = self.push_node(x);
+                self.link_child(idx, d, new_idx);
+                self.live += 1;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Append a node to the arena, honouring `growth_chunk`, and return its index.
+    fn push_node(&mut self, x: u64) -> usize {
+        if self.growth_chunk > 0 && self.nodes.len() == self.nodes.capacity() {
+            self.nodes.reserve_exact(self.growth_chunk);
+        }
+        self.nodes.push(BKNode::new(x));
+        self.nodes.len() - 1
+    }
+
+    /// Record `child` under `parent` at distance `d`, tracking map growth.
+    fn link_child(&mut self, parent: usize, d: u32, child: usize) {
+        let children = &mut self.nodes[parent].children;
+        let before = children.capacity();
+        if children.is_empty() {
+            children.reserve(self.child_capacity);
+        }
+        children.insert(d, child);
+        self.child_bytes += (children.capacity() - before) * (std::mem::size_of::<(u32, usize)>() + 1);
+    }
+
+    /// Insert `x` and return `(inserted, neighbours)` from a single traversal.
+    ///
+    /// Equivalent to `find_all_within(x, radius)` followed by `add(x)`, but the
+    /// insertion path is followed inside the query: every node on it lies in the
+    /// query band, so its distance to `x` is computed only once. `neighbours`
+    /// never contains `x` itself.
+    pub fn add_and_find_within(&mut self, x: u64, radius: u32) -> (bool, Vec<u64>) {
+        if self.nodes.is_empty() {
+            return (self.add(x), Vec::new());
+        }
+
+        let mut results = Vec::new();
+        // next node on the insertion path, and where the path ends
+        let mut path = Some(0usize);
+        let mut matched = None;
+        let mut attach = None;
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.val);
+            if d <= radius && !node.deleted && node.val != x {
+                results.push(node.val);
+            }
+            if path == Some(idx) {
+                path = None;
+                if node.val == x {
+                    matched = Some(idx);
+                } else {
+                    match node.children.get(&d) {
+                        Some(&child_idx) => path = Some(child_idx),
+                        None => attach = Some((idx, d)),
+                    }
+                }
+            }
+            let (lo, hi) = distance_band(d, radius);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+
+        let inserted = match (matched, attach) {
+            (Some(idx), _) if self.nodes[idx].deleted => {
+                self.nodes[idx].deleted = false;
+                self.live += 1;
+                true
+            }
+            (Some(_), _) => false,
+            (None, Some((parent, d))) => {
+                let new_idx = self.push_node(x);
+                self.link_child(parent, d, new_idx);
+                self.live += 1;
+                true
+            }
+            (None, None) => unreachable!("insertion path always ends at a match or a free edge"),
+        };
+        (inserted, results)
+    }
+
+    /// Remove every value, keeping the metric.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.live = 0;
+        self.child_bytes = 0;
+    }
+
+    /// Remove a hash value. Returns false if it was not stored.
+    ///
+    /// The node is tombstoned rather than unlinked, since its children are keyed
+    /// by their distance to it. Call `compact` to reclaim tombstoned slots.
+    pub fn remove(&mut self, x: u64) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut idx = 0;
+        loop {
+            let d = self.metric.distance(x, self.nodes[idx].val);
+            if self.nodes[idx].val == x {
+                if self.nodes[idx].deleted {
+                    return false;
+                }
+                self.nodes[idx].deleted = true;
+                self.live -= 1;
                 return true;
             }
+            match self.nodes[idx].children.get(&d) {
+                Some(&child_idx) => idx = child_idx,
+                None => return false,
+            }
         }
     }
 
@@ -68,12 +503,11 @@ impl BKTreeInner {
         let mut stack = vec![0usize];
         while let Some(idx) = stack.pop() {
             let node = &self.nodes[idx];
-            let d = hamming(x, node.val);
-            if d <= radius {
+            let d = self.metric.distance(x, node.val);
+            if d <= radius && !node.deleted {
                 return true;
             }
-            let lo = d.saturating_sub(radius);
-            let hi = d + radius;
+            let (lo, hi) = distance_band(d, radius);
             for (&dd, &child_idx) in &node.children {
                 if dd >= lo && dd <= hi {
                     stack.push(child_idx);
@@ -93,12 +527,11 @@ impl BKTreeInner {
         let mut stack = vec![0usize];
         while let Some(idx) = stack.pop() {
             let node = &self.nodes[idx];
-            let d = hamming(x, node.val);
-            if d <= radius {
+            let d = self.metric.distance(x, node.val);
+            if d <= radius && !node.deleted {
                 results.push(node.val);
             }
-            let lo = d.saturating_sub(radius);
-            let hi = d + radius;
+            let (lo, hi) = distance_band(d, radius);
             for (&dd, &child_idx) in &node.children {
                 if dd >= lo && dd <= hi {
                     stack.push(child_idx);
@@ -108,12 +541,482 @@ impl BKTreeInner {
         results
     }
 
+    /// `find_all_within` with the results sorted as `order` asks.
+    ///
+    /// Sorting here saves every caller from re-sorting; `Unsorted` costs
+    /// exactly as much as `find_all_within`.
+    pub fn find_all_within_ordered(&self, x: u64, radius: u32, order: ResultOrder) -> Vec<u64> {
+        match order {
+            ResultOrder::Unsorted => self.find_all_within(x, radius),
+            ResultOrder::ValueAscending => {
+                let mut results = self.find_all_within(x, radius);
+                results.sort_unstable();
+                results
+            }
+            ResultOrder::Insertion | ResultOrder::DistanceAscending => {
+                let mut hits = self.find_all_within_indexed(x, radius);
+                if order == ResultOrder::Insertion {
+                    hits.sort_unstable_by_key(|&(idx, _)| idx);
+                } else {
+                    hits.sort_unstable_by_key(|&(idx, d)| (d, self.nodes[idx].val));
+                }
+                hits.into_iter().map(|(idx, _)| self.nodes[idx].val).collect()
+            }
+        }
+    }
+
+    /// Arena index and distance of every live value within `radius` of `x`.
+    fn find_all_within_indexed(&self, x: u64, radius: u32) -> Vec<(usize, u32)> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        let mut results = Vec::new();
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.val);
+            if d <= radius && !node.deleted {
+                results.push((idx, d));
+            }
+            let (lo, hi) = distance_band(d, radius);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+        results
+    }
+
+    /// Like `find_all_within`, but never includes `x` itself.
+    pub fn find_others_within(&self, x: u64, radius: u32) -> Vec<u64> {
+        let mut results = self.find_all_within(x, radius);
+        results.retain(|&v| v != x);
+        results
+    }
+
+    /// Return up to `max` values within distance `radius` of `x`.
+    ///
+    /// Traversal stops as soon as `max` results are collected, so the subset
+    /// is arbitrary rather than the closest matches.
+    pub fn find_some_within(&self, x: u64, radius: u32, max: usize) -> Vec<u64> {
+        if self.nodes.is_empty() || max == 0 {
+            return Vec::new();
+        }
+
+        let mut results = Vec::with_capacity(max.min(self.live));
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.val);
+            if d <= radius && !node.deleted {
+                results.push(node.val);
+                if results.len() == max {
+                    break;
+                }
+            }
+            let (lo, hi) = distance_band(d, radius);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+        results
+    }
+
+    /// Values at the smallest radius `r <= base_radius` that matches anything,
+    /// at most `density_cap` of them.
+    ///
+    /// Equivalent to growing the radius from 0 until the first hit, so dense
+    /// regions answer with a tight radius and sparse ones fall back towards
+    /// `base_radius`. Every result lies at exactly the nearest distance, which
+    /// one branch-and-bound `nearest` search finds before a single capped
+    /// range query collects the ties. Empty if nothing is within `base_radius`.
+    pub fn find_all_within_adaptive(&self, x: u64, base_radius: u32, density_cap: usize) -> Vec<u64> {
+        match self.nearest(x) {
+            Some((_, r)) if r <= base_radius => self.find_some_within(x, r, density_cap),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Closest live value to `x` and its distance, or `None` if the tree is empty.
+    ///
+    /// Branch and bound: the best distance found so far narrows the child band.
+    /// Returns `x` itself at distance 0 if it is stored.
+    pub fn nearest(&self, x: u64) -> Option<(u64, u32)> {
+        self.nearest_excluding(x, None)
+    }
+
+    fn nearest_excluding(&self, x: u64, exclude: Option<u64>) -> Option<(u64, u32)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut best: Option<(u64, u32)> = None;
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.val);
+            if !node.deleted && Some(node.val) != exclude && best.is_none_or(|(_, b)| d < b) {
+                best = Some((node.val, d));
+            }
+            let bound = best.map_or(u32::MAX, |(_, b)| b);
+            let (lo, hi) = distance_band(d, bound);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+        best
+    }
+
+    /// The `k` groups whose closest live member is nearest to `x`, as
+    /// `(group, distance)` sorted by distance then group.
+    ///
+    /// `group` maps each value to its group (e.g. a cluster root). Branch and
+    /// bound as in `nearest`, except that the bound is the k-th best distance
+    /// over distinct groups, so a group with many close members does not
+    /// crowd out the others.
+    pub fn nearest_groups<K, F>(&self, x: u64, k: usize, mut group: F) -> Vec<(K, u32)>
+    where
+        K: Copy + Ord + std::hash::Hash,
+        F: FnMut(u64) -> K,
+    {
+        if self.nodes.is_empty() || k == 0 {
+            return Vec::new();
+        }
+        // at most k groups, each with its best distance so far
+        let mut best: HashMap<K, u32> = HashMap::with_capacity(k + 1);
+        let mut bound = u32::MAX;
+        let mut stack = vec![0usize];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let d = self.metric.distance(x, node.val);
+            if !node.deleted && (d < bound || best.len() < k) {
+                let g = group(node.val);
+                if best.get(&g).is_none_or(|&b| d < b) {
+                    best.insert(g, d);
+                    if best.len() > k {
+                        let worst = best.iter().max_by_key(|(&g, &b)| (b, g)).map(|(&g, _)| g);
+                        best.remove(&worst.expect("map is not empty"));
+                    }
+                    if best.len() == k {
+                        bound = best.values().copied().max().expect("map is not empty");
+                    }
+                }
+            }
+            let (lo, hi) = distance_band(d, bound);
+            for (&dd, &child_idx) in &node.children {
+                if dd >= lo && dd <= hi {
+                    stack.push(child_idx);
+                }
+            }
+        }
+        let mut result: Vec<(K, u32)> = best.into_iter().collect();
+        result.sort_unstable_by_key(|&(g, d)| (d, g));
+        result
+    }
+
+    /// Map from nearest-neighbour distance to the number of live values with it.
+    ///
+    /// Each value's nearest other value is found with one branch-and-bound
+    /// query, so this costs n queries. Useful for picking a radius from data.
+    pub fn nearest_neighbor_distance_histogram(&self) -> HashMap<u32, usize> {
+        let mut hist = HashMap::new();
+        for x in self.values() {
+            if let Some((_, d)) = self.nearest_excluding(x, Some(x)) {
+                *hist.entry(d).or_insert(0) += 1;
+            }
+        }
+        hist
+    }
+
+    /// Return all values whose Hamming distance to `x` lies in `[lo, hi]`.
+    ///
+    /// Traversal prunes with `hi`; `lo` only filters collected values. An empty
+    /// band (`lo > hi`) yields no results.
+    pub fn find_in_band(&self, x: u64, lo: u32, hi: u32) -> Vec<u64> {
+        if lo > hi {
+            return Vec::new();
+        }
+        let mut results = self.find_all_within(x, hi);
+        results.retain(|&v| self.metric.distance(x, v) >= lo);
+        results
+    }
+
+    /// Position of the first element of `xs` with a stored value within `radius`.
+    ///
+    /// Stops at the first hit, so later candidates are never queried.
+    pub fn first_within(&self, xs: &[u64], radius: u32) -> Option<usize> {
+        xs.iter().position(|&x| self.any_within(x, radius))
+    }
+
+    /// Return the `candidates` within distance `radius` of `x`, in input order.
+    ///
+    /// Checks each candidate directly; the tree contents are not consulted.
+    pub fn filter_within(&self, candidates: &[u64], x: u64, radius: u32) -> Vec<u64> {
+        candidates
+            .iter()
+            .copied()
+            .filter(|&c| self.metric.distance(x, c) <= radius)
+            .collect()
+    }
+
+    /// True if any value occupies more than one arena slot, tombstones included.
+    ///
+    /// Duplicates are only rejected along the insertion path, so this scans the
+    /// whole arena as a guard against insertion bugs. Debugging aid; O(n).
+    pub fn has_duplicates(&self) -> bool {
+        let mut seen = std::collections::HashSet::with_capacity(self.nodes.len());
+        !self.nodes.iter().all(|n| seen.insert(n.val))
+    }
+
+    /// Verify structural invariants in O(n). Intended for tests and debugging.
+    ///
+    /// Checks that every child-edge key equals the metric distance between
+    /// parent and child, that each non-root node has exactly one parent, and
+    /// that the live count matches the non-tombstoned nodes.
+    pub fn debug_check_invariants(&self) -> Result<(), String> {
+        let mut parents = vec![0usize; self.nodes.len()];
+        for (idx, node) in self.nodes.iter().enumerate() {
+            for (&dd, &child_idx) in &node.children {
+                let child = self
+                    .nodes
+                    .get(child_idx)
+                    .ok_or_else(|| format!("node {idx} has child index {child_idx} out of bounds"))?;
+                let actual = self.metric.distance(node.val, child.val);
+                if dd != actual {
+                    return Err(format!("edge {idx}->{child_idx} keyed {dd} but distance is {actual}"));
+                }
+                parents[child_idx] += 1;
+            }
+        }
+        if let Some(&root_parents) = parents.first() {
+            if root_parents != 0 {
+                return Err("root node has a parent".to_string());
+            }
+        }
+        if let Some(idx) = parents.iter().skip(1).position(|&p| p != 1) {
+            return Err(format!("node {} has {} parents", idx + 1, parents[idx + 1]));
+        }
+        let live = self.nodes.iter().filter(|n| !n.deleted).count();
+        if live != self.live {
+            return Err(format!("live count {} but {live} live nodes", self.live));
+        }
+        Ok(())
+    }
+
+    /// Node values visited by an exact lookup of `x`, root first.
+    ///
+    /// Follows the child keyed by each node's distance to `x`. Ends at the
+    /// node holding `x` (tombstoned or not), or at the node a new `x` would
+    /// hang off. Bounded by tree depth.
+    pub fn path_to(&self, x: u64) -> Vec<u64> {
+        let mut path = Vec::new();
+        let mut next = (!self.nodes.is_empty()).then_some(0usize);
+        while let Some(idx) = next {
+            let node = &self.nodes[idx];
+            path.push(node.val);
+            next = if node.val == x {
+                None
+            } else {
+                node.children.get(&self.metric.distance(x, node.val)).copied()
+            };
+        }
+        path
+    }
+
+    /// Live values in arena order.
+    pub fn values(&self) -> impl Iterator<Item = u64> + '_ {
+        self.nodes.iter().filter(|n| !n.deleted).map(|n| n.val)
+    }
+
+    /// Number of stored (non-removed) values.
     pub fn len(&self) -> usize {
-        self.nodes.len()
+        self.live
     }
 
     pub fn is_empty(&self) -> bool {
-        self.nodes.is_empty()
+        self.live == 0
+    }
+
+    /// Number of live nodes in the arena (same as `len`).
+    pub fn live_nodes(&self) -> usize {
+        self.live
+    }
+
+    /// Number of arena slots in use, including tombstones left by `remove`.
+    pub fn capacity_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Approximate heap footprint of the arena in bytes, including each
+    /// node's child map (entries plus one control byte per bucket).
+    ///
+    /// O(1): child-map growth is tallied as nodes are linked.
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.nodes.capacity() * std::mem::size_of::<BKNode>() + self.child_bytes
+    }
+
+    /// Depth and branching statistics, from one walk over the arena.
+    pub fn stats(&self) -> TreeStats {
+        if self.nodes.is_empty() {
+            return TreeStats::default();
+        }
+        let mut stats = TreeStats {
+            nodes: self.nodes.len(),
+            ..TreeStats::default()
+        };
+        let mut depth_sum = 0usize;
+        let mut internal = 0usize;
+        let mut stack = vec![(0usize, 0usize)];
+        while let Some((idx, depth)) = stack.pop() {
+            let children = &self.nodes[idx].children;
+            depth_sum += depth;
+            stats.max_depth = stats.max_depth.max(depth);
+            if children.is_empty() {
+                stats.leaves += 1;
+            } else {
+                internal += 1;
+            }
+            stack.extend(children.values().map(|&child| (child, depth + 1)));
+        }
+        stats.mean_depth = depth_sum as f64 / stats.nodes as f64;
+        if internal > 0 {
+            stats.mean_branching = (stats.nodes - 1) as f64 / internal as f64;
+        }
+        stats
+    }
+
+    /// Number of child edges using each distance key, tombstones included.
+    ///
+    /// A narrow band of keys suggests a small fixed array could replace the
+    /// per-node children map.
+    pub fn child_distance_histogram(&self) -> HashMap<u32, usize> {
+        let mut hist = HashMap::new();
+        for &d in self.nodes.iter().flat_map(|n| n.children.keys()) {
+            *hist.entry(d).or_insert(0) += 1;
+        }
+        hist
+    }
+
+    /// Classify the tree shape, reporting `TooSmall` below `HEALTH_MIN_NODES`.
+    pub fn health(&self) -> TreeHealth {
+        self.health_with_min_nodes(HEALTH_MIN_NODES)
+    }
+
+    /// True when the mean node depth exceeds twice log2 of the node count.
+    ///
+    /// Signals that rebuilding with `from_slice_balanced` before a heavy query
+    /// phase is likely to pay off. Always false below `HEALTH_MIN_NODES`.
+    pub fn should_rebalance(&self) -> bool {
+        let stats = self.stats();
+        stats.nodes >= HEALTH_MIN_NODES && stats.mean_depth > 2.0 * (stats.nodes as f64).log2()
+    }
+
+    /// Like `health`, with a custom node threshold for `TooSmall`.
+    ///
+    /// A tree is `Degenerate` when its deepest path holds more than half the
+    /// nodes: insertion order has produced a near-chain and queries no longer
+    /// prune. Tiny trees often look like this by chance, hence the threshold.
+    pub fn health_with_min_nodes(&self, min_nodes: usize) -> TreeHealth {
+        let stats = self.stats();
+        if stats.nodes < min_nodes.max(1) {
+            TreeHealth::TooSmall
+        } else if 2 * (stats.max_depth + 1) > stats.nodes {
+            TreeHealth::Degenerate
+        } else {
+            TreeHealth::Healthy
+        }
+    }
+
+    /// Uniform random sample of `min(k, len)` live values, reproducible from `seed`.
+    ///
+    /// Reservoir sampling over one arena walk; uses O(k) extra memory.
+    pub fn sample(&self, k: usize, seed: u64) -> Vec<u64> {
+        let mut rng = SplitMix64::new(seed);
+        let mut reservoir = Vec::with_capacity(k.min(self.live));
+        for (seen, val) in self.nodes.iter().filter(|n| !n.deleted).map(|n| n.val).enumerate() {
+            if seen < k {
+                reservoir.push(val);
+            } else {
+                let j = rng.below(seen as u64 + 1) as usize;
+                if j < k {
+                    reservoir[j] = val;
+                }
+            }
+        }
+        reservoir
+    }
+
+    /// Combine two trees, inserting the smaller one's live values into the larger.
+    ///
+    /// The result holds the union of both value sets and keeps the larger
+    /// tree's metric and settings; both trees are expected to share a metric.
+    pub fn merge(self, other: Self) -> Self {
+        let (mut base, small) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        base.reserve(small.len());
+        for x in small.values() {
+            base.add(x);
+        }
+        base
+    }
+
+    /// Renumber arena nodes in breadth-first order from the root.
+    ///
+    /// Insertion order scatters a node's children across the arena; after this
+    /// pass siblings are adjacent and upper levels are packed at the front, so
+    /// queries touch fewer cache lines. Tombstones are kept. Query results are
+    /// unchanged; only internal node indices move.
+    pub fn optimize_layout(&mut self) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut order = Vec::with_capacity(self.nodes.len());
+        order.push(0usize);
+        let mut head = 0;
+        while head < order.len() {
+            let mut children: Vec<(u32, usize)> =
+                self.nodes[order[head]].children.iter().map(|(&d, &c)| (d, c)).collect();
+            children.sort_unstable();
+            order.extend(children.into_iter().map(|(_, c)| c));
+            head += 1;
+        }
+        let mut new_index = vec![0usize; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+        let mut old_nodes: Vec<Option<BKNode>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        self.nodes.reserve_exact(order.len());
+        for old in order {
+            let mut node = old_nodes[old].take().expect("each node is reached once");
+            for child in node.children.values_mut() {
+                *child = new_index[*child];
+            }
+            self.nodes.push(node);
+        }
+    }
+
+    /// Rebuild the arena without tombstoned slots.
+    ///
+    /// Live values are reinserted in their existing arena order, so query results
+    /// are unchanged; only internal node indices are renumbered.
+    pub fn compact(&mut self) {
+        if self.live == self.nodes.len() {
+            return;
+        }
+        let old = std::mem::take(&mut self.nodes);
+        self.live = 0;
+        self.child_bytes = 0;
+        self.nodes.reserve(old.iter().filter(|n| !n.deleted).count());
+        for node in old.into_iter().filter(|n| !n.deleted) {
+            self.add(node.val);
+        }
     }
 }
 
@@ -121,6 +1024,43 @@ impl BKTreeInner {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_add_or_get() {
+        let mut tree = BKTreeInner::new();
+        assert_eq!(tree.add_or_get(5), Ok(()));
+        assert_eq!(tree.add_or_get(6), Ok(()));
+        assert_eq!(tree.add_or_get(6), Err(6));
+        tree.remove(6);
+        assert_eq!(tree.add_or_get(6), Ok(())); // revived
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    fn test_max_radius_matches_all_without_overflow() {
+        let mut tree = BKTreeInner::new();
+        for x in [0u64, 1, 0xFF, u64::MAX] {
+            tree.add(x);
+        }
+        assert!(tree.any_within(7, u32::MAX));
+        assert_eq!(tree.find_all_within(7, u32::MAX).len(), 4);
+        assert_eq!(tree.find_in_band(7, 0, u32::MAX).len(), 4);
+        let (added, neighbors) = tree.add_and_find_within(7, u32::MAX);
+        assert!(added);
+        assert_eq!(neighbors.len(), 4);
+    }
+
+    #[test]
+    fn test_zero_extended_u32_hashes() {
+        // 32-bit hashes stored as u64 keep 32-bit distances; radius 32 matches all
+        let mut tree = BKTreeInner::new();
+        for x in [0u32, u32::MAX, 0x0F0F_0F0F] {
+            tree.add(x.into());
+        }
+        assert_eq!(hamming(u32::MAX.into(), 0), 32);
+        assert_eq!(tree.find_all_within(0, 32).len(), 3);
+        assert_eq!(tree.find_all_within(0, 31).len(), 2);
+    }
+
     #[test]
     fn test_empty_tree() {
         let tree = BKTreeInner::new();
@@ -137,6 +1077,87 @@ mod tests {
         assert_eq!(tree.len(), 1);
     }
 
+    #[test]
+    fn test_has_duplicates() {
+        let mut tree = BKTreeInner::new();
+        for x in [5, 1, 5, 9, 1] {
+            tree.add(x);
+        }
+        assert!(!tree.has_duplicates());
+        tree.nodes.push(BKNode::new(9)); // bypass add
+        assert!(tree.has_duplicates());
+    }
+
+    #[test]
+    fn test_add_and_find_within_matches_separate_calls() {
+        let values = [0b0000, 0b0001, 0b0011, 0b0111, 0b1111, 0b1000_0001, 0b0110];
+        let mut fused = BKTreeInner::new();
+        let mut plain = BKTreeInner::new();
+        for &x in &values {
+            let mut expected = plain.find_all_within(x, 2);
+            expected.retain(|&v| v != x);
+            expected.sort();
+            let (inserted, mut got) = fused.add_and_find_within(x, 2);
+            got.sort();
+            assert_eq!(inserted, plain.add(x));
+            assert_eq!(got, expected);
+        }
+        assert!(!fused.add_and_find_within(0b0011, 2).0); // duplicate
+        assert!(fused.remove(0b0011));
+        assert!(fused.add_and_find_within(0b0011, 2).0); // revived
+        assert!(fused.debug_check_invariants().is_ok());
+        assert_eq!(fused.len(), values.len());
+    }
+
+    #[test]
+    fn test_remove_and_revive() {
+        let mut tree = BKTreeInner::new();
+        tree.add(0b0000);
+        tree.add(0b0001);
+        tree.add(0b0011);
+        assert!(tree.remove(0b0000)); // root becomes a tombstone
+        assert!(!tree.remove(0b0000));
+        assert!(!tree.remove(0b1111)); // never stored
+        assert_eq!(tree.len(), 2);
+        assert!(!tree.find_all_within(0b0000, 0).contains(&0b0000));
+        assert!(tree.any_within(0b0011, 0)); // still reachable through the tombstone
+
+        assert!(tree.add(0b0000)); // revived in place
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.capacity_nodes(), 3);
+    }
+
+    #[test]
+    fn test_compact_drops_tombstones() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000, 0b0001, 0b0011, 0b0111, 0b1111] {
+            tree.add(x);
+        }
+        tree.remove(0b0000);
+        tree.remove(0b0111);
+        assert_eq!(tree.live_nodes(), 3);
+        assert_eq!(tree.capacity_nodes(), 5);
+
+        let mut before = tree.find_all_within(0b0000, 64);
+        tree.compact();
+        let mut after = tree.find_all_within(0b0000, 64);
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+        assert_eq!(tree.capacity_nodes(), 3);
+    }
+
+    #[test]
+    fn test_child_capacity_preallocates_internal_nodes() {
+        let mut tree = BKTreeInner::new().with_child_capacity(8);
+        tree.add(0b0000);
+        assert_eq!(tree.nodes[0].children.capacity(), 0); // leaves stay unallocated
+        tree.add(0b0001);
+        assert!(tree.nodes[0].children.capacity() >= 8);
+        assert_eq!(tree.nodes[1].children.capacity(), 0);
+        assert_eq!(tree.find_all_within(0b0000, 1).len(), 2);
+    }
+
     #[test]
     fn test_any_within() {
         let mut tree = BKTreeInner::new();
@@ -163,6 +1184,117 @@ mod tests {
         assert!(results.contains(&0b0011));
     }
 
+    #[test]
+    fn test_find_others_within() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000, 0b0001, 0b0011] {
+            tree.add(x);
+        }
+        let mut others = tree.find_others_within(0b0000, 2);
+        others.sort();
+        assert_eq!(others, vec![0b0001, 0b0011]);
+        assert_eq!(tree.find_others_within(0b0111, 1), vec![0b0011]); // absent query
+    }
+
+    #[test]
+    fn test_distance_band_saturates() {
+        assert_eq!(distance_band(5, 2), (3, 7));
+        assert_eq!(distance_band(1, 3), (0, 4));
+        assert_eq!(distance_band(10, u32::MAX), (0, u32::MAX));
+    }
+
+    #[test]
+    fn test_find_all_within_adaptive() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000_0011, 0b0000_0101, 0b0000_0110, 0b1111_0000] {
+            tree.add(x);
+        }
+        // nothing at distance 0 or 1 from 0; three ties at distance 2
+        let mut found = tree.find_all_within_adaptive(0, 5, 10);
+        found.sort();
+        assert_eq!(found, vec![0b0000_0011, 0b0000_0101, 0b0000_0110]);
+        assert_eq!(tree.find_all_within_adaptive(0, 5, 2).len(), 2);
+        assert_eq!(tree.find_all_within_adaptive(0b0000_0011, 5, 10), vec![0b0000_0011]);
+        assert!(tree.find_all_within_adaptive(0, 1, 10).is_empty());
+        assert!(BKTreeInner::new().find_all_within_adaptive(0, 64, 10).is_empty());
+    }
+
+    #[test]
+    fn test_bitflip_counts() {
+        assert_eq!(neighbors_within_bitflips(7, 0).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(neighbors_within_bitflips(7, 1).count(), 1 + 64);
+        assert_eq!(neighbors_within_bitflips(7, 2).count(), 1 + 64 + 2016);
+        assert_eq!(neighbors_within_bitflips(7, 3).count(), 1 + 64 + 2016 + 41664);
+    }
+
+    #[test]
+    fn test_bitflips_exact_distance() {
+        let x = 0xDEAD_BEEF_u64;
+        let all: Vec<u64> = neighbors_within_bitflips(x, 2).collect();
+        assert!(all.iter().all(|&y| hamming(x, y) <= 2));
+        let unique: std::collections::HashSet<u64> = all.iter().copied().collect();
+        assert_eq!(unique.len(), all.len());
+    }
+
+    #[test]
+    fn test_find_in_band() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000, 0b0001, 0b0011, 0b0111, 0b1111] {
+            tree.add(x);
+        }
+        let mut band = tree.find_in_band(0b0000, 1, 3);
+        band.sort();
+        assert_eq!(band, vec![0b0001, 0b0011, 0b0111]);
+        assert_eq!(tree.find_in_band(0b0000, 0, 0), vec![0b0000]);
+        assert!(tree.find_in_band(0b0000, 3, 1).is_empty());
+    }
+
+    #[test]
+    fn test_masked_metric_keeps_zero_distance_values() {
+        use crate::distance::MaskedHamming;
+
+        let mut tree = BKTreeInner::with_metric(MaskedHamming { mask: 0xF });
+        assert!(tree.add(0x10));
+        assert!(tree.add(0x1F)); // distance 0 under the mask, but a distinct value
+        assert!(!tree.add(0x1F));
+        assert_eq!(tree.len(), 2);
+        let mut found = tree.find_all_within(0x13, 0);
+        found.sort();
+        assert_eq!(found, vec![0x10, 0x1F]);
+        assert!(tree.remove(0x1F));
+        assert_eq!(tree.find_all_within(0x13, 0), vec![0x10]);
+        assert!(tree.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_filter_within() {
+        let tree = BKTreeInner::new();
+        let candidates = [0b1111, 0b0001, 0b0000, 0b0011];
+        assert_eq!(tree.filter_within(&candidates, 0b0000, 1), vec![0b0001, 0b0000]);
+        assert!(tree.filter_within(&[], 0, 64).is_empty());
+    }
+
+    #[test]
+    fn test_debug_check_invariants() {
+        let mut tree = BKTreeInner::new();
+        assert!(tree.debug_check_invariants().is_ok());
+        for x in [0b0000, 0b0001, 0b0011, 0b0111, 0b1111] {
+            tree.add(x);
+        }
+        tree.remove(0b0011);
+        assert!(tree.debug_check_invariants().is_ok());
+
+        tree.nodes[1].val = 0b1110_0000; // corrupt a child value
+        assert!(tree.debug_check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_pairwise_hamming() {
+        let m = pairwise_hamming(&[0b0000, 0b0011, 0b1111]);
+        assert_eq!(m, vec![vec![0, 2, 4], vec![2, 0, 2], vec![4, 2, 0]]);
+        assert!(pairwise_hamming(&[]).is_empty());
+    }
+
     #[test]
     fn test_hamming_distance() {
         assert_eq!(hamming(0, 0), 0);
@@ -171,4 +1303,340 @@ mod tests {
         assert_eq!(hamming(0b1100, 0b1010), 2);
         assert_eq!(hamming(u64::MAX, 0), 64);
     }
+
+    #[test]
+    fn test_stats_and_health() {
+        let mut chain = BKTreeInner::new();
+        chain.add(0);
+        // every single-bit value is distance 1 from the root and 2 from each
+        // other, so each one descends the previous one's 2-edge
+        for bit in 0..64 {
+            chain.add(1u64 << bit);
+        }
+        let stats = chain.stats();
+        assert_eq!(stats.nodes, 65);
+        assert_eq!(stats.max_depth, 64);
+        assert_eq!(stats.leaves, 1);
+        assert_eq!(chain.health(), TreeHealth::Degenerate);
+        assert_eq!(chain.health_with_min_nodes(100), TreeHealth::TooSmall);
+
+        let mut spread = BKTreeInner::new();
+        for i in 0..500u64 {
+            spread.add(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        assert_eq!(spread.health(), TreeHealth::Healthy);
+        assert!(spread.stats().mean_branching > 1.0);
+
+        let mut tiny = BKTreeInner::new();
+        tiny.add(0);
+        tiny.add(1);
+        assert_eq!(tiny.health(), TreeHealth::TooSmall);
+        assert_eq!(BKTreeInner::new().stats(), TreeStats::default());
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut tree = BKTreeInner::new();
+        for x in 0..100u64 {
+            tree.add(x);
+        }
+        tree.remove(5);
+        let sample = tree.sample(10, 42);
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sample, tree.sample(10, 42));
+        assert!(sample.iter().all(|&x| x < 100 && x != 5));
+        let mut deduped = sample.clone();
+        deduped.sort_unstable();
+        deduped.dedup();
+        assert_eq!(deduped.len(), 10);
+
+        let mut all = tree.sample(1000, 0);
+        all.sort_unstable();
+        assert_eq!(all, (0..100).filter(|&x| x != 5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_first_within() {
+        let mut tree = BKTreeInner::new();
+        tree.add(0b0000);
+        assert_eq!(tree.first_within(&[0b1111, 0b0011, 0b0001, 0b0000], 2), Some(1));
+        assert_eq!(tree.first_within(&[0b1111], 2), None);
+        assert_eq!(tree.first_within(&[], 64), None);
+    }
+
+    #[test]
+    fn test_arena_growth_controls() {
+        let mut sized = BKTreeInner::with_capacity(100);
+        let cap = sized.nodes.capacity();
+        assert!(cap >= 100);
+        for x in 0..100u64 {
+            sized.add(x);
+        }
+        assert_eq!(sized.nodes.capacity(), cap); // never reallocated
+
+        let mut chunked = BKTreeInner::new().with_growth_chunk(10);
+        for x in 0..25u64 {
+            chunked.add(x);
+        }
+        assert_eq!(chunked.nodes.capacity(), 30);
+        assert_eq!(chunked.len(), 25);
+    }
+
+    #[test]
+    fn test_child_distance_histogram() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000, 0b0001, 0b0011, 0b0010, 0b1111] {
+            tree.add(x);
+        }
+        // root 0000 has children at 1 (0001), 2 (0011), 4 (1111); 0010 hangs off 0001 at 2
+        let hist = tree.child_distance_histogram();
+        assert_eq!(hist.get(&1), Some(&1));
+        assert_eq!(hist.get(&2), Some(&2));
+        assert_eq!(hist.get(&4), Some(&1));
+        assert_eq!(hist.values().sum::<usize>(), tree.capacity_nodes() - 1);
+        assert!(BKTreeInner::new().child_distance_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_optimize_layout_preserves_queries() {
+        let mut tree = BKTreeInner::new();
+        for i in 0..300u64 {
+            tree.add(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        tree.remove(0x9E37_79B9_7F4A_7C15);
+        let queries: Vec<u64> = (0..20u64).map(|i| i.wrapping_mul(0xD1B5_4A32_D192_ED03)).collect();
+        let before: Vec<Vec<u64>> = queries
+            .iter()
+            .map(|&q| {
+                let mut r = tree.find_all_within(q, 30);
+                r.sort_unstable();
+                r
+            })
+            .collect();
+        tree.optimize_layout();
+        for (&q, expected) in queries.iter().zip(&before) {
+            let mut r = tree.find_all_within(q, 30);
+            r.sort_unstable();
+            assert_eq!(&r, expected);
+        }
+        assert_eq!(tree.len(), 299);
+        assert!(tree.debug_check_invariants().is_ok());
+        // breadth-first: every child sits after its parent
+        for (idx, node) in tree.nodes.iter().enumerate() {
+            assert!(node.children.values().all(|&c| c > idx));
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut tree = BKTreeInner::new();
+        assert_eq!(tree.nearest(0), None);
+        for x in [0b0000_0000, 0b0000_0111, 0b1111_0000] {
+            tree.add(x);
+        }
+        assert_eq!(tree.nearest(0b0000_0011), Some((0b0000_0111, 1)));
+        assert_eq!(tree.nearest(0b1111_0000), Some((0b1111_0000, 0)));
+        tree.remove(0b1111_0000);
+        assert_eq!(tree.nearest(0b1111_0000), Some((0b0000_0000, 4)));
+    }
+
+    #[test]
+    fn test_find_all_within_ordered() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0111, 0b0000, 0b0011, 0b1000, 0b0001] {
+            tree.add(x);
+        }
+        let q = 0b0001;
+        let ordered = |order| tree.find_all_within_ordered(q, 2, order);
+        assert_eq!(
+            ordered(ResultOrder::Insertion),
+            [0b0111, 0b0000, 0b0011, 0b1000, 0b0001]
+        );
+        assert_eq!(
+            ordered(ResultOrder::ValueAscending),
+            [0b0000, 0b0001, 0b0011, 0b0111, 0b1000]
+        );
+        assert_eq!(
+            ordered(ResultOrder::DistanceAscending),
+            [0b0001, 0b0000, 0b0011, 0b0111, 0b1000]
+        );
+        let mut unsorted = ordered(ResultOrder::Unsorted);
+        unsorted.sort_unstable();
+        assert_eq!(unsorted, ordered(ResultOrder::ValueAscending));
+        assert_eq!("distance".parse::<ResultOrder>(), Ok(ResultOrder::DistanceAscending));
+        assert!("random".parse::<ResultOrder>().is_err());
+    }
+
+    #[test]
+    fn test_nearest_groups() {
+        let mut tree = BKTreeInner::new();
+        assert!(tree.nearest_groups(0, 3, |v| v).is_empty());
+        // group = high byte; group 0 has several close members
+        for x in [0x00_00, 0x00_01, 0x00_03, 0x01_0F, 0x03_FF, 0xFF_FF] {
+            tree.add(x);
+        }
+        let by_byte = |v: u64| v >> 8;
+        assert_eq!(tree.nearest_groups(0, 2, by_byte), vec![(0x00, 0), (0x01, 5)]);
+        assert_eq!(
+            tree.nearest_groups(0, 3, by_byte),
+            vec![(0x00, 0), (0x01, 5), (0x03, 10)]
+        );
+        assert_eq!(tree.nearest_groups(0, 9, by_byte).len(), 4);
+        assert!(tree.nearest_groups(0, 0, by_byte).is_empty());
+
+        // brute force agrees on random data
+        let mut rng = SplitMix64::new(11);
+        let mut tree = BKTreeInner::new();
+        let xs: Vec<u64> = (0..500).map(|_| rng.next_u64() & 0xFFFF).collect();
+        for &x in &xs {
+            tree.add(x);
+        }
+        let group = |v: u64| v % 37;
+        for q in [0u64, 0x1234, 0xFFFF] {
+            let mut brute: HashMap<u64, u32> = HashMap::new();
+            for x in tree.values() {
+                let d = hamming(q, x);
+                brute.entry(group(x)).and_modify(|b| *b = (*b).min(d)).or_insert(d);
+            }
+            let mut brute: Vec<(u64, u32)> = brute.into_iter().collect();
+            brute.sort_unstable_by_key(|&(g, d)| (d, g));
+            let got = tree.nearest_groups(q, 5, group);
+            let dists: Vec<u32> = got.iter().map(|&(_, d)| d).collect();
+            let want: Vec<u32> = brute[..5].iter().map(|&(_, d)| d).collect();
+            assert_eq!(dists, want);
+            assert!(got.iter().all(|g| brute.contains(g)));
+        }
+    }
+
+    #[test]
+    fn test_distance_histogram_to() {
+        let mut tree = BKTreeInner::new();
+        for x in [0u64, 1, 2, 3, u64::MAX] {
+            tree.add(x);
+        }
+        tree.remove(2);
+        let hist = tree.distance_histogram_to(0);
+        assert_eq!((hist[0], hist[1], hist[2], hist[64]), (1, 1, 1, 1));
+        assert_eq!(hist.iter().sum::<usize>(), tree.len());
+        assert_eq!(BKTreeInner::new().distance_histogram_to(0), [0; 65]);
+    }
+
+    #[test]
+    fn test_nearest_neighbor_distance_histogram() {
+        let mut tree = BKTreeInner::new();
+        for x in [0b0000, 0b0001, 0b1111_0000] {
+            tree.add(x);
+        }
+        let hist = tree.nearest_neighbor_distance_histogram();
+        assert_eq!(hist.get(&1), Some(&2)); // 0000 <-> 0001
+        assert_eq!(hist.get(&4), Some(&1)); // 11110000 -> 0000
+        assert_eq!(hist.len(), 2);
+
+        let mut single = BKTreeInner::new();
+        single.add(7);
+        assert!(single.nearest_neighbor_distance_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_find_some_within() {
+        let mut tree = BKTreeInner::new();
+        for x in 0..64u64 {
+            tree.add(1 << x);
+        }
+        let some = tree.find_some_within(0, 1, 5);
+        assert_eq!(some.len(), 5);
+        assert!(some.iter().all(|&v| hamming(0, v) <= 1));
+        assert_eq!(tree.find_some_within(0, 1, 100).len(), 64);
+        assert!(tree.find_some_within(0, 1, 0).is_empty());
+    }
+
+    #[test]
+    fn test_hamming_is_const() {
+        const TABLE: [u32; 4] = [hamming(0, 0), hamming(0, 1), hamming(0, 3), hamming(0, u64::MAX)];
+        assert_eq!(TABLE, [0, 1, 2, 64]);
+    }
+
+    #[test]
+    fn test_path_to() {
+        let mut tree = BKTreeInner::new();
+        assert!(tree.path_to(1).is_empty());
+        tree.add(0);
+        for bit in 0..4 {
+            tree.add(1 << bit); // chain: 0 -> 1 -> 2 -> 4 -> 8
+        }
+        assert_eq!(tree.path_to(4), vec![0, 1, 2, 4]);
+        assert_eq!(tree.path_to(0), vec![0]);
+        // 16 is absent and would attach below 8
+        assert_eq!(tree.path_to(16), vec![0, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_check_radius() {
+        let tree = BKTreeInner::new().with_max_radius(Some(8));
+        assert_eq!(tree.max_radius(), Some(8));
+        assert!(tree.check_radius(8).is_ok());
+        assert_eq!(
+            tree.check_radius(9),
+            Err(GamecovError::RadiusTooLarge { radius: 9, max: 8 })
+        );
+        assert!(tree.check_radius(9).unwrap_err().to_string().contains("max_radius 8"));
+        assert!(BKTreeInner::new().check_radius(u32::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_should_rebalance_and_balanced_build() {
+        let mut chain = BKTreeInner::new();
+        chain.add(0);
+        for bit in 0..64 {
+            chain.add(1u64 << bit);
+        }
+        assert!(chain.should_rebalance());
+
+        let xs: Vec<u64> = (0..500u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15)).collect();
+        let tree = BKTreeInner::from_slice_balanced(&xs, 7);
+        assert!(!tree.should_rebalance());
+        assert_eq!(tree.len(), 500);
+        assert!(xs.iter().all(|&x| tree.any_within(x, 0)));
+        let again = BKTreeInner::from_slice_balanced(&xs, 7);
+        assert_eq!(tree.values().collect::<Vec<_>>(), again.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_merge_into_larger() {
+        let tree_of = |xs: &[u64]| {
+            let mut tree = BKTreeInner::new();
+            for &x in xs {
+                tree.add(x);
+            }
+            tree
+        };
+        let big: Vec<u64> = (0..100).collect();
+        // either argument order inserts the small tree into the big one; 5 overlaps
+        for merged in [
+            tree_of(&big).merge(tree_of(&[5, 1000])),
+            tree_of(&[1000]).merge(tree_of(&big)),
+        ] {
+            assert_eq!(merged.len(), 101);
+            assert_eq!(merged.path_to(0), vec![0]); // big tree's root kept
+            assert!(merged.any_within(1000, 0));
+            assert!(!merged.has_duplicates());
+        }
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let mut tree = BKTreeInner::new();
+        for x in [8u64, 0, 3, u64::MAX, 12] {
+            tree.add(x);
+        }
+        tree.remove(3);
+        let mut buf = Vec::new();
+        tree.save(&mut buf).unwrap();
+        let loaded = BKTreeInner::load(buf.as_slice()).unwrap();
+        assert_eq!(loaded.values().collect::<Vec<_>>(), tree.values().collect::<Vec<_>>());
+        assert_eq!(loaded.path_to(12), tree.path_to(12));
+
+        buf[20] ^= 0xFF;
+        assert!(matches!(BKTreeInner::load(buf.as_slice()), Err(GamecovError::Format(e)) if e.contains("checksum")));
+    }
 }
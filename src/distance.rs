@@ -0,0 +1,163 @@
+use crate::bktree::hamming;
+use crate::error::GamecovError;
+
+/// Width of the fingerprints stored by the Hamming structures.
+pub const HASH_BITS: u32 = u64::BITS;
+
+/// Distance metric over u64 fingerprints used by the BK-tree and tracker.
+///
+/// BK-tree pruning is exact for any metric satisfying the triangle inequality.
+/// Distinct values may be at distance 0 (a pseudometric); the tree then stores
+/// them as separate nodes linked by a 0-keyed edge.
+pub trait Distance {
+    /// True only for plain Hamming distance, where distance 0 implies equality
+    /// and bit-level shortcuts are valid.
+    const IS_HAMMING: bool = false;
+
+    fn distance(&self, a: u64, b: u64) -> u32;
+
+    /// Largest distance the metric can report, for normalizing distances to
+    /// `[0, 1]`. Defaults to the hash width.
+    fn max_distance(&self) -> u32 {
+        HASH_BITS
+    }
+}
+
+/// Plain Hamming distance (POPCNT of the XOR).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hamming;
+
+impl Distance for Hamming {
+    const IS_HAMMING: bool = true;
+
+    #[inline(always)]
+    fn distance(&self, a: u64, b: u64) -> u32 {
+        hamming(a, b)
+    }
+}
+
+/// Hamming distance that ignores the bits set in `mask`.
+///
+/// Useful when some fingerprint bits encode metadata such as timestamps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaskedHamming {
+    pub mask: u64,
+}
+
+impl Distance for MaskedHamming {
+    #[inline(always)]
+    fn distance(&self, a: u64, b: u64) -> u32 {
+        ((a ^ b) & !self.mask).count_ones()
+    }
+}
+
+/// Hamming distance where each differing bit `i` contributes `weights[i]`.
+///
+/// For hashes whose bit positions matter unequally, such as a phash whose
+/// low-frequency coefficients are more stable than the rest. A sum of
+/// per-bit weights over differing bits is an L1 metric, so the BK-tree's
+/// triangle-inequality pruning stays exact with no extra slack in the band;
+/// zero weights make it a pseudometric, which the tree also supports. The
+/// sum saturates at `u32::MAX`, which preserves the triangle inequality.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightedHamming {
+    pub weights: [u32; 64],
+}
+
+impl Distance for WeightedHamming {
+    #[inline]
+    fn distance(&self, a: u64, b: u64) -> u32 {
+        let mut diff = a ^ b;
+        let mut sum = 0u32;
+        while diff != 0 {
+            sum = sum.saturating_add(self.weights[diff.trailing_zeros() as usize]);
+            diff &= diff - 1;
+        }
+        sum
+    }
+
+    /// Sum of all weights: the distance between complementary hashes.
+    fn max_distance(&self) -> u32 {
+        self.weights.iter().fold(0u32, |sum, &w| sum.saturating_add(w))
+    }
+}
+
+/// Hamming radius matching a similarity fraction over `bits`-bit hashes.
+///
+/// Computes `round((1 - sim) * bits)`, so `sim = 0.95` over 64 bits is a
+/// radius of 3. Works for any bit-counting fingerprint (simhash, phash,
+/// b-bit minhash signatures). `sim` is clamped to `[0, 1]`.
+pub fn radius_for_similarity(sim: f64, bits: u32) -> u32 {
+    ((1.0 - sim.clamp(0.0, 1.0)) * f64::from(bits)).round() as u32
+}
+
+/// Reject a radius wider than `bits`-bit hashes.
+///
+/// Such a radius is legal for the data structures, where it saturates to
+/// "everything matches", but is almost always a unit mistake (a percentage
+/// or a distance for longer hashes), so callers at API boundaries check it.
+pub fn check_radius_width(radius: u32, bits: u32) -> Result<(), GamecovError> {
+    if radius > bits {
+        return Err(GamecovError::RadiusExceedsWidth { radius, bits });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hamming_metric() {
+        assert_eq!(Hamming.distance(0b1010, 0b0101), 4);
+        assert_eq!(Hamming.distance(7, 7), 0);
+    }
+
+    #[test]
+    fn test_weighted_hamming() {
+        let mut weights = [1; 64];
+        assert_eq!(WeightedHamming { weights }.distance(0b1010, 0b0101), 4);
+        weights[0] = 10;
+        weights[3] = 0;
+        let metric = WeightedHamming { weights };
+        assert_eq!(metric.distance(0b1010, 0b0101), 10 + 1 + 1);
+        assert_eq!(metric.distance(0, 0b1000), 0);
+        assert_eq!(metric.max_distance(), 10 + 62);
+        assert_eq!(metric.max_distance(), metric.distance(0, u64::MAX));
+        assert_eq!(Hamming.max_distance(), 64);
+        assert_eq!(
+            WeightedHamming {
+                weights: [u32::MAX; 64]
+            }
+            .distance(0, 3),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_radius_for_similarity() {
+        assert_eq!(radius_for_similarity(0.95, 64), 3);
+        assert_eq!(radius_for_similarity(0.9, 32), 3);
+        assert_eq!(radius_for_similarity(1.0, 64), 0);
+        assert_eq!(radius_for_similarity(0.0, 64), 64);
+        assert_eq!(radius_for_similarity(1.5, 64), 0);
+    }
+
+    #[test]
+    fn test_check_radius_width() {
+        assert_eq!(check_radius_width(64, HASH_BITS), Ok(()));
+        assert_eq!(
+            check_radius_width(100, HASH_BITS),
+            Err(GamecovError::RadiusExceedsWidth { radius: 100, bits: 64 })
+        );
+        assert!(check_radius_width(33, 32).is_err());
+    }
+
+    #[test]
+    fn test_masked_hamming_ignores_masked_bits() {
+        let metric = MaskedHamming { mask: 0xFF };
+        assert_eq!(metric.distance(0x00, 0xFF), 0);
+        assert_eq!(metric.distance(0x100, 0x0FF), 1);
+        assert_eq!(metric.distance(u64::MAX, 0), 56);
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// Errors returned by fallible operations across the crate.
+///
+/// Methods whose preconditions the caller cannot always check up front (a key
+/// being registered, a radius being in range, a file being intact) return this
+/// instead of panicking, so the library can run inside a long-lived process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GamecovError {
+    /// The key was never registered with the structure.
+    KeyNotFound(u64),
+    /// A query radius exceeds the tree's configured `max_radius`.
+    RadiusTooLarge { radius: u32, max: u32 },
+    /// A radius exceeds the hash width, so every pair of hashes would match.
+    RadiusExceedsWidth { radius: u32, bits: u32 },
+    /// `increase_radius` was asked for a smaller radius.
+    RadiusDecrease { current: u32, requested: u32 },
+    /// Two trackers that must share a radius do not.
+    RadiusMismatch { left: u32, right: u32 },
+    /// An argument is outside its documented domain.
+    InvalidArgument(String),
+    /// Saved data could not be read back: I/O failure, wrong kind or version, or corruption.
+    Format(String),
+}
+
+impl fmt::Display for GamecovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeyNotFound(x) => write!(f, "key {x} is not registered"),
+            Self::RadiusTooLarge { radius, max } => {
+                write!(f, "radius {radius} exceeds this tree's max_radius {max}")
+            }
+            Self::RadiusExceedsWidth { radius, bits } => {
+                write!(
+                    f,
+                    "radius {radius} exceeds the {bits}-bit hash width, so every hash would match"
+                )
+            }
+            Self::RadiusDecrease { current, requested } => {
+                write!(f, "increase_radius cannot shrink radius {current} to {requested}")
+            }
+            Self::RadiusMismatch { left, right } => write!(f, "trackers have different radii {left} and {right}"),
+            Self::InvalidArgument(msg) | Self::Format(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for GamecovError {}
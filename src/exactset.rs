@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use crate::error::GamecovError;
+
+/// Largest `universe` accepted for [`ExactBackend::BitSet`]: a 512 MiB bitmap.
+pub const MAX_BITSET_UNIVERSE: u64 = 1 << 32;
+
+/// Storage used by a tracker's exact-duplicate set.
+///
+/// `HashSet` costs roughly 16 bytes per stored hash whatever the values are,
+/// and wins when hashes are sparse in a large space (the usual 64-bit
+/// perceptual hash). `BitSet` costs one bit per possible value below
+/// `universe`, allocated up front, and wins when hashes come from a small
+/// dense range such as a phash quantized to 20-odd bits: it is smaller once
+/// more than about 1 in 128 values of the range is stored, and a lookup is a
+/// single word read instead of a hash probe. Values at or above `universe`
+/// still work and fall back to a `HashSet`. `universe` is capped at
+/// [`MAX_BITSET_UNIVERSE`] since the bitmap is allocated eagerly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExactBackend {
+    #[default]
+    HashSet,
+    BitSet {
+        universe: u64,
+    },
+}
+
+/// Set of u64 hashes with a bitmap for values below a fixed universe and a
+/// `HashSet` for everything else.
+#[derive(Clone, Debug, Default)]
+pub struct ExactSet {
+    words: Vec<u64>,
+    universe: u64,
+    /// Number of bits set in `words`.
+    dense_len: usize,
+    overflow: HashSet<u64>,
+}
+
+impl ExactSet {
+    /// Create an empty set, rejecting a bitset universe above [`MAX_BITSET_UNIVERSE`].
+    pub fn new(backend: ExactBackend) -> Result<Self, GamecovError> {
+        let universe = match backend {
+            ExactBackend::HashSet => 0,
+            ExactBackend::BitSet { universe } if universe > MAX_BITSET_UNIVERSE => {
+                return Err(GamecovError::InvalidArgument(format!(
+                    "bitset universe {universe} exceeds the maximum {MAX_BITSET_UNIVERSE}"
+                )));
+            }
+            ExactBackend::BitSet { universe } => universe,
+        };
+        Ok(Self {
+            words: vec![0; universe.div_ceil(64) as usize],
+            universe,
+            dense_len: 0,
+            overflow: HashSet::new(),
+        })
+    }
+
+    pub fn backend(&self) -> ExactBackend {
+        if self.universe == 0 {
+            ExactBackend::HashSet
+        } else {
+            ExactBackend::BitSet {
+                universe: self.universe,
+            }
+        }
+    }
+
+    /// Insert `x`. Returns true if it was not already present.
+    pub fn insert(&mut self, x: u64) -> bool {
+        if x >= self.universe {
+            return self.overflow.insert(x);
+        }
+        let (word, bit) = ((x / 64) as usize, 1u64 << (x % 64));
+        let fresh = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        self.dense_len += usize::from(fresh);
+        fresh
+    }
+
+    /// Remove `x`. Returns true if it was present.
+    pub fn remove(&mut self, x: u64) -> bool {
+        if x >= self.universe {
+            return self.overflow.remove(&x);
+        }
+        let (word, bit) = ((x / 64) as usize, 1u64 << (x % 64));
+        let present = self.words[word] & bit != 0;
+        self.words[word] &= !bit;
+        self.dense_len -= usize::from(present);
+        present
+    }
+
+    pub fn contains(&self, x: &u64) -> bool {
+        if *x >= self.universe {
+            return self.overflow.contains(x);
+        }
+        self.words[(*x / 64) as usize] & (1u64 << (*x % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense_len + self.overflow.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every stored value: the dense range in ascending order, then the rest
+    /// in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let dense = self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                (rest != 0).then(|| {
+                    let bit = rest.trailing_zeros();
+                    rest &= rest - 1;
+                    i as u64 * 64 + u64::from(bit)
+                })
+            })
+        });
+        dense.chain(self.overflow.iter().copied())
+    }
+
+    /// Approximate heap footprint in bytes: the bitmap plus the overflow table
+    /// (entries and one control byte per bucket).
+    pub fn memory_bytes(&self) -> usize {
+        self.words.capacity() * 8 + self.overflow.capacity() * (std::mem::size_of::<u64>() + 1)
+    }
+
+    /// Reserve room for `additional` more values outside the bitmap, which is
+    /// allocated in full up front.
+    pub fn reserve(&mut self, additional: usize) {
+        self.overflow.reserve(additional);
+    }
+
+    /// Release overflow-table capacity left behind by `remove`.
+    pub fn shrink_to_fit(&mut self) {
+        self.overflow.shrink_to_fit();
+    }
+
+    /// Remove every value, keeping the bitmap allocation.
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+        self.dense_len = 0;
+        self.overflow.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_agree() {
+        let values = [0u64, 63, 64, 99, 5, 5, 1000, u64::MAX, 63];
+        let mut hashed = ExactSet::new(ExactBackend::HashSet).unwrap();
+        let mut dense = ExactSet::new(ExactBackend::BitSet { universe: 100 }).unwrap();
+        for x in values {
+            assert_eq!(hashed.insert(x), dense.insert(x));
+        }
+        assert_eq!(dense.len(), 7);
+        assert_eq!(hashed.len(), dense.len());
+        for x in [0, 5, 63, 64, 99, 100, 1000, u64::MAX] {
+            assert_eq!(hashed.contains(&x), dense.contains(&x));
+        }
+        let mut a: Vec<u64> = hashed.iter().collect();
+        let mut b: Vec<u64> = dense.iter().collect();
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+
+        for x in [64, 1000, 7] {
+            assert_eq!(hashed.remove(x), dense.remove(x));
+        }
+        assert_eq!(dense.len(), 5);
+        assert!(!dense.contains(&64) && !dense.contains(&1000));
+
+        dense.clear();
+        assert!(dense.is_empty());
+        assert!(!dense.contains(&64));
+        assert_eq!(dense.backend(), ExactBackend::BitSet { universe: 100 });
+    }
+
+    #[test]
+    fn test_universe_cap() {
+        assert!(ExactSet::new(ExactBackend::BitSet { universe: 1 << 63 }).is_err());
+        let cap = ExactBackend::BitSet {
+            universe: MAX_BITSET_UNIVERSE + 1,
+        };
+        assert!(ExactSet::new(cap)
+            .unwrap_err()
+            .to_string()
+            .contains("exceeds the maximum"));
+    }
+}
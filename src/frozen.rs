@@ -0,0 +1,140 @@
+use crate::bktree::{distance_band, hamming};
+
+/// Read-only Hamming BK-tree packed into flat parallel arrays.
+///
+/// Built by [`BKTreeInner::into_frozen_compact`](crate::bktree::BKTreeInner::into_frozen_compact).
+/// Nodes are in breadth-first order and node `i`'s children occupy
+/// `child_start[i]..child_start[i + 1]` of `child_dist`/`child_index`, sorted
+/// by distance. Hamming distances of u64 never exceed 64, so edge keys fit in
+/// a `u8`, and indices are `u32`. That is about 17 bytes per node against
+/// several times that for a `HashMap` per node, at the cost of supporting only
+/// neighbour queries: no insertion or removal.
+pub struct FrozenCompactTree {
+    values: Vec<u64>,
+    child_start: Vec<u32>,
+    child_dist: Vec<u8>,
+    child_index: Vec<u32>,
+}
+
+impl FrozenCompactTree {
+    pub(crate) fn from_parts(
+        values: Vec<u64>,
+        child_start: Vec<u32>,
+        child_dist: Vec<u8>,
+        child_index: Vec<u32>,
+    ) -> Self {
+        debug_assert_eq!(child_start.len(), values.len() + 1);
+        debug_assert_eq!(child_dist.len(), child_index.len());
+        Self {
+            values,
+            child_start,
+            child_dist,
+            child_index,
+        }
+    }
+
+    /// Check if any value is within Hamming distance `radius` of `x`.
+    pub fn any_within(&self, x: u64, radius: u32) -> bool {
+        let mut found = false;
+        self.visit_within(x, radius, |_| {
+            found = true;
+            false
+        });
+        found
+    }
+
+    /// Return all values within Hamming distance `radius` of `x`.
+    pub fn find_all_within(&self, x: u64, radius: u32) -> Vec<u64> {
+        let mut results = Vec::new();
+        self.visit_within(x, radius, |v| {
+            results.push(v);
+            true
+        });
+        results
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Bytes held by the four arrays, including unused capacity.
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.values.capacity() * std::mem::size_of::<u64>()
+            + self.child_start.capacity() * std::mem::size_of::<u32>()
+            + self.child_dist.capacity()
+            + self.child_index.capacity() * std::mem::size_of::<u32>()
+    }
+
+    /// Call `hit` on each value within `radius` of `x` until it returns false.
+    fn visit_within(&self, x: u64, radius: u32, mut hit: impl FnMut(u64) -> bool) {
+        if self.values.is_empty() {
+            return;
+        }
+        let mut stack = vec![0u32];
+        while let Some(idx) = stack.pop() {
+            let idx = idx as usize;
+            let d = hamming(x, self.values[idx]);
+            if d <= radius && !hit(self.values[idx]) {
+                return;
+            }
+            let (lo, hi) = distance_band(d, radius);
+            let span = self.child_start[idx] as usize..self.child_start[idx + 1] as usize;
+            for (&dd, &child) in self.child_dist[span.clone()].iter().zip(&self.child_index[span]) {
+                let dd = u32::from(dd);
+                if dd > hi {
+                    break; // children are sorted by distance
+                }
+                if dd >= lo {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bktree::BKTreeInner;
+    use crate::rng::SplitMix64;
+
+    #[test]
+    fn test_frozen_matches_arena_and_is_at_most_half_the_size() {
+        let mut rng = SplitMix64::new(7);
+        let xs: Vec<u64> = (0..5_000).map(|_| rng.next_u64() & 0xFFFF_FFFF).collect();
+        let mut tree = BKTreeInner::new();
+        for &x in &xs {
+            tree.add(x);
+        }
+        tree.remove(xs[0]); // tombstones are dropped when freezing
+        let arena_bytes = tree.memory_bytes();
+        let mut expected: Vec<Vec<u64>> = xs[..50].iter().map(|&q| tree.find_all_within(q, 8)).collect();
+
+        let frozen = tree.into_frozen_compact();
+        assert_eq!(frozen.len(), xs.len() - 1);
+        for (q, want) in xs[..50].iter().zip(&mut expected) {
+            let mut got = frozen.find_all_within(*q, 8);
+            got.sort_unstable();
+            want.sort_unstable();
+            assert_eq!(&got, want);
+            assert_eq!(frozen.any_within(*q, 8), !want.is_empty());
+        }
+        assert!(
+            2 * frozen.memory_bytes() <= arena_bytes,
+            "frozen {} bytes vs arena {arena_bytes} bytes",
+            frozen.memory_bytes()
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let frozen = BKTreeInner::new().into_frozen_compact();
+        assert!(frozen.is_empty());
+        assert!(!frozen.any_within(0, 64));
+        assert!(frozen.find_all_within(0, 64).is_empty());
+    }
+}
@@ -1,8 +1,28 @@
+from typing import Literal, TypedDict
+
+import numpy as np
+import numpy.typing as npt
+
 class BKTree:
-    def __init__(self) -> None: ...
+    def __init__(self, *, max_radius: int | None = None, allow_saturating: bool = False) -> None: ...
+    @property
+    def max_radius(self) -> int | None: ...
     def add(self, x: int) -> bool: ...
+    def add_bytes(self, b: bytes) -> bool: ...
     def any_within(self, x: int, radius: int) -> bool: ...
-    def find_all_within(self, x: int, radius: int) -> list[int]: ...
+    def any_within_bytes(self, b: bytes, radius: int) -> bool: ...
+    def find_all_within(
+        self,
+        x: int,
+        radius: int,
+        order: Literal["unsorted", "insertion", "value", "distance"] | None = None,
+    ) -> list[int]: ...
+    def find_others_within(self, x: int, radius: int) -> list[int]: ...
+    def find_some_within(self, x: int, radius: int, max: int) -> list[int]: ...
+    def first_within(self, xs: list[int], radius: int) -> int | None: ...
+    def find_in_band(self, x: int, lo: int, hi: int) -> list[int]: ...
+    def filter_within(self, candidates: list[int], x: int, radius: int) -> list[int]: ...
+    def sample(self, k: int, seed: int) -> list[int]: ...
     def __len__(self) -> int: ...
 
 class UnionFind:
@@ -12,12 +32,166 @@ class UnionFind:
     def union(self, a: int, b: int) -> None: ...
     @property
     def component_count(self) -> int: ...
+    def flat_labeling(self) -> tuple[npt.NDArray[np.uint64], npt.NDArray[np.uint64]]: ...
+
+class AddEventDict(TypedDict):
+    hash: int
+    was_new: bool
+    neighbor_count: int
+    merged: bool
+    components_after: int
 
 class CoverageTracker:
-    def __init__(self, radius: int) -> None: ...
+    def __init__(
+        self,
+        radius: int | None = None,
+        *,
+        similarity: float | None = None,
+        hash_bits: int = 64,
+        record_history: bool = False,
+        history_mode: Literal["off", "live", "full"] | None = None,
+        over_connected_fraction: float = 0.5,
+        exact_universe: int | None = None,
+        dedup_radius: int = 0,
+        count_occurrences: bool = False,
+        deterministic: bool = False,
+        record_events: bool = False,
+        allow_saturating: bool = False,
+    ) -> None: ...
+    def add_hash(self, x: int) -> bool: ...
+    def add_hashes_mask(self, xs: list[int]) -> npt.NDArray[np.bool_]: ...
+    def add_hashes_events(self, xs: list[int]) -> list[AddEventDict]: ...
+    def add_hash_neighbors(self, x: int) -> int: ...
+    def add_hash_increased(self, x: int) -> bool: ...
+    def add_hash_cluster(self, x: int) -> int: ...
+    def manual_union(self, a: int, b: int) -> bool: ...
+    def query_context(self, x: int, radius: int) -> list[tuple[int, int]]: ...
+    def novelty_score(self, x: int) -> float: ...
+    def would_bridge(self, x: int) -> bool: ...
+    @property
+    def coverage_count(self) -> int: ...
+    def coverage_count_at(self, radius: int) -> int: ...
+    def coverage_count_min_size(self, min: int) -> int: ...
+    @property
+    def coverage_entropy(self) -> float: ...
+    @property
+    def largest_cluster_fraction(self) -> float: ...
+    @property
+    def is_over_connected(self) -> bool: ...
+    @property
+    def over_connected_fraction(self) -> float: ...
+    @property
+    def last_add_merged(self) -> bool: ...
+    def recent_new_clusters(self, window: int) -> int: ...
+    @property
+    def dedup_radius(self) -> int: ...
+    @property
+    def union_calls(self) -> int: ...
+    @property
+    def union_merges(self) -> int: ...
+    def memory_bytes(self) -> int: ...
+    def shrink_to_fit(self) -> None: ...
+    def set_memory_budget(self, bytes: int) -> None: ...
+    @property
+    def evicted_count(self) -> int: ...
+    @property
+    def edge_count(self) -> int: ...
+    @property
+    def total_unique(self) -> int: ...
+    @staticmethod
+    def from_hashes(xs: list[int], radius: int, *, allow_saturating: bool = False) -> CoverageTracker: ...
+    @staticmethod
+    def with_capacity(radius: int, n: int, *, allow_saturating: bool = False) -> CoverageTracker: ...
+    def export_hashes(self) -> npt.NDArray[np.uint64]: ...
+    def unique_hashes(self) -> npt.NDArray[np.uint64]: ...
+    def flat_labeling(self) -> tuple[npt.NDArray[np.uint64], npt.NDArray[np.uint64]]: ...
+    def labeled_chunk(self, start: int, size: int) -> tuple[npt.NDArray[np.uint64], npt.NDArray[np.uint64]]: ...
+    def cluster_size_percentiles(self, ps: list[float]) -> list[int]: ...
+    def occurrence_count(self, x: int) -> int: ...
+    def most_frequent(self, k: int) -> list[tuple[int, int]]: ...
+    def nearest_clusters(self, x: int, k: int) -> list[tuple[int, int]]: ...
+    def top_clusters(self, k: int) -> list[tuple[int, int]]: ...
+    def nearest_neighbor_distance_histogram(self) -> dict[int, int]: ...
+    def distance_histogram_to(self, reference: int) -> list[int]: ...
+    def summary_json(self) -> str: ...
+    def debug_state(self) -> dict[str, int]: ...
+    def rebuild_tree(self) -> None: ...
+    @property
+    def coverage_history(self) -> list[int]: ...
+    @property
+    def history_points(self) -> list[tuple[int, int, int]]: ...
+    def event_log(self) -> list[tuple[int, bool, int]]: ...
+    @property
+    def episode_starts(self) -> list[int]: ...
+    def diff(self, other: CoverageTracker) -> CoverageDiff: ...
+    def swap(self, other: CoverageTracker) -> None: ...
+    def fork(self) -> CoverageTracker: ...
+    def into_bktree(self) -> BKTree: ...
+    def reset(self) -> None: ...
+    def reset_keep_history(self) -> None: ...
+
+class ShardedCoverageTracker:
+    def __init__(self, radius: int, num_shards: int = 16, *, allow_saturating: bool = False) -> None: ...
+    def add_hash(self, x: int) -> bool: ...
+    @property
+    def num_shards(self) -> int: ...
+    @property
+    def coverage_count(self) -> int: ...
+    @property
+    def total_unique(self) -> int: ...
+    def reset(self) -> None: ...
+
+class WindowedCoverageTracker:
+    def __init__(self, radius: int, window: int, *, allow_saturating: bool = False) -> None: ...
     def add_hash(self, x: int) -> bool: ...
+    def add_hash_windowed(self, x: int) -> tuple[bool, int | None]: ...
+    @property
+    def window(self) -> int: ...
     @property
     def coverage_count(self) -> int: ...
     @property
     def total_unique(self) -> int: ...
     def reset(self) -> None: ...
+
+class BKTree32:
+    def __init__(self, *, allow_saturating: bool = False) -> None: ...
+    def add(self, x: int) -> bool: ...
+    def add_bytes(self, b: bytes) -> bool: ...
+    def any_within(self, x: int, radius: int) -> bool: ...
+    def any_within_bytes(self, b: bytes, radius: int) -> bool: ...
+    def find_all_within(self, x: int, radius: int) -> list[int]: ...
+    def __len__(self) -> int: ...
+
+class CoverageTracker32:
+    def __init__(self, radius: int, *, allow_saturating: bool = False) -> None: ...
+    def add_hash(self, x: int) -> bool: ...
+    @property
+    def coverage_count(self) -> int: ...
+    @property
+    def total_unique(self) -> int: ...
+    def reset(self) -> None: ...
+
+class CoverageDiff:
+    @property
+    def only_in_self(self) -> list[int]: ...
+    @property
+    def only_in_other(self) -> list[int]: ...
+    @property
+    def clusters_only_in_self(self) -> list[list[int]]: ...
+    @property
+    def clusters_only_in_other(self) -> list[list[int]]: ...
+    def __bool__(self) -> bool: ...
+
+class DedupReport:
+    @property
+    def total(self) -> int: ...
+    @property
+    def exact_unique(self) -> int: ...
+    @property
+    def clusters_at_radius(self) -> int: ...
+    @property
+    def largest_cluster(self) -> int: ...
+
+def pairwise_hamming(xs: list[int]) -> list[list[int]]: ...
+def analyze(xs: list[int], radius: int) -> DedupReport: ...
+def radius_for_similarity(sim: float, bits: int = 64) -> int: ...
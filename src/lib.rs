@@ -1,43 +1,165 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict};
 
+pub mod bkmap;
 pub mod bktree;
+pub mod distance;
+pub mod error;
+pub mod exactset;
+pub mod frozen;
 pub mod monitor;
+pub mod persist;
+pub mod rng;
+pub mod sharded;
 pub mod unionfind;
+pub mod windowed;
 
 use bktree::BKTreeInner;
-use monitor::CoverageTrackerInner;
+use distance::HASH_BITS;
+use error::GamecovError;
+use exactset::ExactBackend;
+use monitor::{CoverageTrackerInner, HistoryMode};
+use sharded::ShardedCoverageTrackerInner;
 use unionfind::UnionFindInner;
+use windowed::WindowedCoverageTrackerInner;
 
 // ── Python wrappers ───────────────────────────────────────────────────────
 
+impl From<GamecovError> for PyErr {
+    fn from(err: GamecovError) -> Self {
+        match err {
+            GamecovError::KeyNotFound(_) => PyKeyError::new_err(err.to_string()),
+            _ => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+/// Convert a Vec into a 1-D numpy array of the given dtype.
+fn to_numpy<'py, T>(py: Python<'py>, data: Vec<T>, dtype: &str) -> PyResult<Bound<'py, PyAny>>
+where
+    T: IntoPyObject<'py>,
+{
+    let kwargs = [("dtype", dtype)].into_py_dict(py)?;
+    py.import("numpy")?.call_method("asarray", (data,), Some(&kwargs))
+}
+
+/// Check that a digest buffer is exactly `N` bytes long.
+///
+/// Digests are read big-endian (most significant byte first), the order in
+/// which hashing libraries serialize and print them.
+fn digest<const N: usize>(b: &[u8]) -> PyResult<[u8; N]> {
+    b.try_into()
+        .map_err(|_| PyValueError::new_err(format!("expected a {N}-byte hash digest, got {} bytes", b.len())))
+}
+
+/// Reject a radius above the 64-bit hash width unless `allow_saturating` is set.
+fn check_width(radius: u32, allow_saturating: bool) -> PyResult<()> {
+    check_width_bits(radius, HASH_BITS, allow_saturating)
+}
+
+/// `check_width` for hashes `bits` wide.
+fn check_width_bits(radius: u32, bits: u32, allow_saturating: bool) -> PyResult<()> {
+    if allow_saturating {
+        return Ok(());
+    }
+    distance::check_radius_width(radius, bits)
+        .map_err(|e| PyValueError::new_err(format!("{e}; pass allow_saturating=True if that is intended")))
+}
+
 /// BK-tree for Hamming-distance queries on 64-bit perceptual hashes.
+///
+/// Radii above 64 raise ValueError unless constructed with allow_saturating=True.
 #[pyclass]
 struct BKTree {
     inner: BKTreeInner,
+    allow_saturating: bool,
 }
 
 #[pymethods]
 impl BKTree {
     #[new]
-    fn new() -> Self {
+    #[pyo3(signature = (*, max_radius=None, allow_saturating=false))]
+    fn new(max_radius: Option<u32>, allow_saturating: bool) -> Self {
         Self {
-            inner: BKTreeInner::new(),
+            inner: BKTreeInner::new().with_max_radius(max_radius),
+            allow_saturating,
         }
     }
 
+    #[getter]
+    fn max_radius(&self) -> Option<u32> {
+        self.inner.max_radius()
+    }
+
     /// Insert a hash. Returns True if new, False if exact duplicate.
     fn add(&mut self, x: u64) -> bool {
         self.inner.add(x)
     }
 
+    /// `add` for an 8-byte big-endian digest; raises ValueError on any other length.
+    fn add_bytes(&mut self, b: &[u8]) -> PyResult<bool> {
+        Ok(self.inner.add(u64::from_be_bytes(digest(b)?)))
+    }
+
     /// Check if any stored hash is within Hamming distance `radius` of `x`.
-    fn any_within(&self, x: u64, radius: u32) -> bool {
-        self.inner.any_within(x, radius)
+    fn any_within(&self, x: u64, radius: u32) -> PyResult<bool> {
+        self.check_radius(radius)?;
+        Ok(self.inner.any_within(x, radius))
+    }
+
+    /// `any_within` for an 8-byte big-endian digest.
+    fn any_within_bytes(&self, b: &[u8], radius: u32) -> PyResult<bool> {
+        self.any_within(u64::from_be_bytes(digest(b)?), radius)
     }
 
     /// Return all stored hashes within Hamming distance `radius` of `x`.
-    fn find_all_within(&self, x: u64, radius: u32) -> Vec<u64> {
-        self.inner.find_all_within(x, radius)
+    /// `order` is "unsorted" (default, fastest), "insertion", "value" or "distance".
+    #[pyo3(signature = (x, radius, order=None))]
+    fn find_all_within(&self, x: u64, radius: u32, order: Option<&str>) -> PyResult<Vec<u64>> {
+        self.check_radius(radius)?;
+        let order = order.map(str::parse).transpose()?.unwrap_or_default();
+        Ok(self.inner.find_all_within_ordered(x, radius, order))
+    }
+
+    /// Return all stored hashes other than `x` within Hamming distance `radius` of `x`.
+    fn find_others_within(&self, x: u64, radius: u32) -> PyResult<Vec<u64>> {
+        self.check_radius(radius)?;
+        Ok(self.inner.find_others_within(x, radius))
+    }
+
+    /// Return up to `max` stored hashes within `radius` of `x` (not necessarily the closest).
+    fn find_some_within(&self, x: u64, radius: u32, max: usize) -> PyResult<Vec<u64>> {
+        self.check_radius(radius)?;
+        Ok(self.inner.find_some_within(x, radius, max))
+    }
+
+    /// Index of the first hash in `xs` with a stored hash within `radius`, or None.
+    fn first_within(&self, xs: Vec<u64>, radius: u32) -> PyResult<Option<usize>> {
+        self.check_radius(radius)?;
+        Ok(self.inner.first_within(&xs, radius))
+    }
+
+    /// Return all stored hashes whose distance to `x` lies in `[lo, hi]`.
+    fn find_in_band(&self, x: u64, lo: u32, hi: u32) -> PyResult<Vec<u64>> {
+        if lo > hi {
+            return Err(PyValueError::new_err(format!("empty band: lo ({lo}) > hi ({hi})")));
+        }
+        self.check_radius(hi)?;
+        Ok(self.inner.find_in_band(x, lo, hi))
+    }
+
+    /// Return the candidates within Hamming distance `radius` of `x`, in input order.
+    fn filter_within(&self, candidates: Vec<u64>, x: u64, radius: u32) -> PyResult<Vec<u64>> {
+        self.check_radius(radius)?;
+        Ok(self.inner.filter_within(&candidates, x, radius))
+    }
+
+    /// Uniform random sample of up to k stored hashes, reproducible from `seed`.
+    fn sample(&self, k: usize, seed: u64) -> Vec<u64> {
+        self.inner.sample(k, seed)
     }
 
     fn __len__(&self) -> usize {
@@ -45,6 +167,13 @@ impl BKTree {
     }
 }
 
+impl BKTree {
+    fn check_radius(&self, radius: u32) -> PyResult<()> {
+        check_width(radius, self.allow_saturating)?;
+        Ok(self.inner.check_radius(radius)?)
+    }
+}
+
 /// Disjoint-set (union-find) over u64 keys.
 #[pyclass]
 struct UnionFind {
@@ -64,19 +193,27 @@ impl UnionFind {
         self.inner.make_set(x)
     }
 
-    fn find(&mut self, x: u64) -> u64 {
-        self.inner.find(x)
+    /// Representative of x's set. Raises KeyError if x was never registered.
+    fn find(&mut self, x: u64) -> PyResult<u64> {
+        Ok(self.inner.find(x)?)
     }
 
+    /// Union the sets of a and b. Raises KeyError if either was never registered.
     #[pyo3(name = "union")]
-    fn union_sets(&mut self, a: u64, b: u64) {
-        self.inner.union(a, b)
+    fn union_sets(&mut self, a: u64, b: u64) -> PyResult<()> {
+        Ok(self.inner.union(a, b)?)
     }
 
     #[getter]
     fn component_count(&self) -> usize {
         self.inner.component_count()
     }
+
+    /// Return (keys, labels) numpy uint64 arrays with dense labels 0..k.
+    fn flat_labeling<'py>(&mut self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        let (keys, labels) = self.inner.flat_labeling();
+        Ok((to_numpy(py, keys, "uint64")?, to_numpy(py, labels, "uint64")?))
+    }
 }
 
 /// Combined BK-tree + union-find coverage tracker.
@@ -85,22 +222,601 @@ impl UnionFind {
 #[pyclass]
 struct CoverageTracker {
     inner: CoverageTrackerInner,
+    allow_saturating: bool,
 }
 
 #[pymethods]
 impl CoverageTracker {
     #[new]
-    fn new(radius: u32) -> Self {
+    /// `exact_universe`, if given, backs duplicate detection with a bitset over
+    /// `0..exact_universe`; worthwhile only when hashes come from a small dense range.
+    /// Universes above 2**32 raise ValueError.
+    /// Hashes within `dedup_radius` of a stored hash count as duplicates and are not stored.
+    /// `count_occurrences` enables `occurrence_count` and `most_frequent`.
+    /// `deterministic` makes cluster roots (the smallest hash) reproducible across runs.
+    /// `record_events` enables `event_log`, which grows by one entry per `add_hash` call.
+    /// Pass either `radius` or `similarity`, a fraction in [0, 1] converted with
+    /// `radius_for_similarity(similarity, hash_bits)`.
+    /// Radii above 64 (here and in later calls) raise ValueError unless `allow_saturating`.
+    #[pyo3(signature = (radius=None, *, similarity=None, hash_bits=64, record_history=false, history_mode=None, over_connected_fraction=0.5, exact_universe=None, dedup_radius=0, count_occurrences=false, deterministic=false, record_events=false, allow_saturating=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        radius: Option<u32>,
+        similarity: Option<f64>,
+        hash_bits: u32,
+        record_history: bool,
+        history_mode: Option<&str>,
+        over_connected_fraction: f64,
+        exact_universe: Option<u64>,
+        dedup_radius: u32,
+        count_occurrences: bool,
+        deterministic: bool,
+        record_events: bool,
+        allow_saturating: bool,
+    ) -> PyResult<Self> {
+        let mode = match history_mode {
+            Some(mode) => mode.parse()?,
+            None if record_history => HistoryMode::Live,
+            None => HistoryMode::Off,
+        };
+        let radius = match (radius, similarity) {
+            (Some(radius), None) => radius,
+            (None, Some(sim)) => py_radius_for_similarity(sim, hash_bits)?,
+            _ => return Err(PyValueError::new_err("pass exactly one of radius and similarity")),
+        };
+        check_width(radius, allow_saturating)?;
+        check_width(dedup_radius, allow_saturating)?;
+        Ok(Self {
+            inner: CoverageTrackerInner::new(radius)
+                .with_deterministic(deterministic)
+                .with_history_mode(mode)
+                .with_over_connected_fraction(over_connected_fraction)?
+                .with_dedup_radius(dedup_radius)
+                .with_occurrence_counts(count_occurrences)
+                .with_event_log(record_events)
+                .with_exact_backend(match exact_universe {
+                    Some(universe) => ExactBackend::BitSet { universe },
+                    None => ExactBackend::HashSet,
+                })?,
+            allow_saturating,
+        })
+    }
+
+    /// Insert a hash. Returns True if the hash was new.
+    fn add_hash(&mut self, x: u64) -> bool {
+        self.inner.add_hash(x)
+    }
+
+    /// Insert hashes in order. Returns a numpy bool array marking the ones that were new.
+    fn add_hashes_mask<'py>(&mut self, py: Python<'py>, xs: Vec<u64>) -> PyResult<Bound<'py, PyAny>> {
+        to_numpy(py, self.inner.add_hashes_mask(&xs), "bool")
+    }
+
+    /// Insert hashes in order. Returns one dict per hash with keys hash, was_new,
+    /// neighbor_count, merged and components_after.
+    fn add_hashes_events<'py>(&mut self, py: Python<'py>, xs: Vec<u64>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner
+            .add_hashes_events(&xs)
+            .into_iter()
+            .map(|e| {
+                let event = PyDict::new(py);
+                event.set_item("hash", e.hash)?;
+                event.set_item("was_new", e.was_new)?;
+                event.set_item("neighbor_count", e.neighbor_count)?;
+                event.set_item("merged", e.merged)?;
+                event.set_item("components_after", e.components_after)?;
+                Ok(event)
+            })
+            .collect()
+    }
+
+    /// Insert a hash. Returns how many stored hashes were within radius of it.
+    fn add_hash_neighbors(&mut self, x: u64) -> usize {
+        self.inner.add_hash_neighbors(x)
+    }
+
+    /// Insert a hash. Returns True iff it started a new cluster.
+    fn add_hash_increased(&mut self, x: u64) -> bool {
+        self.inner.add_hash_increased(x)
+    }
+
+    /// Insert a hash. Returns the root hash of its cluster right after insertion.
+    fn add_hash_cluster(&mut self, x: u64) -> u64 {
+        self.inner.add_hash_cluster(x)
+    }
+
+    /// Merge the clusters of `a` and `b` regardless of distance, storing either if absent
+    /// without linking it to its neighbours.
+    /// Returns True if two clusters were merged (coverage_count drops by one).
+    fn manual_union(&mut self, a: u64, b: u64) -> bool {
+        self.inner.manual_union(a, b)
+    }
+
+    /// (neighbour, cluster_root) pairs for stored hashes within `radius` of `x`, without inserting it.
+    fn query_context(&mut self, x: u64, radius: u32) -> PyResult<Vec<(u64, u64)>> {
+        check_width(radius, self.allow_saturating)?;
+        Ok(self.inner.query_context(x, radius))
+    }
+
+    /// Query-only novelty in [0, 1]: 0.5 * nearest distance / 64 + 0.5 / (1 + target cluster size).
+    fn novelty_score(&mut self, x: u64) -> f64 {
+        self.inner.novelty_score(x)
+    }
+
+    /// Check whether inserting `x` would merge two or more existing clusters.
+    fn would_bridge(&self, x: u64) -> bool {
+        self.inner.would_bridge(x)
+    }
+
+    #[getter]
+    fn coverage_count(&self) -> usize {
+        self.inner.coverage_count()
+    }
+
+    /// Number of clusters the stored hashes would form at `radius`; does not change the tracker.
+    fn coverage_count_at(&self, radius: u32) -> PyResult<usize> {
+        check_width(radius, self.allow_saturating)?;
+        Ok(self.inner.coverage_count_at(radius))
+    }
+
+    /// Number of clusters with at least `min` hashes (min=2 ignores singletons).
+    fn coverage_count_min_size(&self, min: usize) -> usize {
+        self.inner.coverage_count_min_size(min)
+    }
+
+    /// Shannon entropy (bits) of the cluster-size distribution.
+    #[getter]
+    fn coverage_entropy(&self) -> f64 {
+        self.inner.coverage_entropy()
+    }
+
+    /// Share of unique hashes held by the largest cluster.
+    #[getter]
+    fn largest_cluster_fraction(&self) -> f64 {
+        self.inner.largest_cluster_fraction()
+    }
+
+    /// True when largest_cluster_fraction exceeds over_connected_fraction (radius likely too large).
+    #[getter]
+    fn is_over_connected(&self) -> bool {
+        self.inner.is_over_connected()
+    }
+
+    #[getter]
+    fn over_connected_fraction(&self) -> f64 {
+        self.inner.over_connected_fraction()
+    }
+
+    /// True if the most recent add reduced the component count: a new hash that
+    /// joined an existing cluster or bridged several.
+    #[getter]
+    fn last_add_merged(&self) -> bool {
+        self.inner.last_add_merged()
+    }
+
+    /// New clusters started by the last `window` add_hash calls (at most 1024
+    /// are kept). Zero over a long window means exploration has stalled.
+    fn recent_new_clusters(&self, window: usize) -> usize {
+        self.inner.recent_new_clusters(window)
+    }
+
+    #[getter]
+    fn dedup_radius(&self) -> u32 {
+        self.inner.dedup_radius()
+    }
+
+    /// Union-find union calls since the last reset.
+    #[getter]
+    fn union_calls(&self) -> u64 {
+        self.inner.union_calls()
+    }
+
+    /// Union-find union calls since the last reset that merged two clusters.
+    #[getter]
+    fn union_merges(&self) -> u64 {
+        self.inner.union_merges()
+    }
+
+    /// Approximate bytes held by the stored hashes (logs excluded).
+    fn memory_bytes(&self) -> usize {
+        self.inner.memory_bytes()
+    }
+
+    /// Release unused capacity in every internal structure, e.g. after `with_capacity`.
+    fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit()
+    }
+
+    /// Keep memory_bytes() under `bytes` by evicting the coldest whole clusters.
+    /// Coverage then reflects a bounded, recently active subset of the stream.
+    fn set_memory_budget(&mut self, bytes: usize) {
+        self.inner.set_memory_budget(bytes)
+    }
+
+    /// Hashes evicted to stay under the memory budget since the last reset.
+    #[getter]
+    fn evicted_count(&self) -> usize {
+        self.inner.evicted_count()
+    }
+
+    /// Number of distinct hash pairs within radius; never decreases as hashes are added.
+    #[getter]
+    fn edge_count(&self) -> usize {
+        self.inner.edge_count()
+    }
+
+    #[getter]
+    fn total_unique(&self) -> usize {
+        self.inner.total_unique()
+    }
+
+    /// Rebuild a tracker from a bare hash set, recomputing clusters at `radius`.
+    #[staticmethod]
+    #[pyo3(signature = (xs, radius, *, allow_saturating=false))]
+    fn from_hashes(py: Python<'_>, xs: Vec<u64>, radius: u32, allow_saturating: bool) -> PyResult<Self> {
+        check_width(radius, allow_saturating)?;
+        Ok(Self {
+            inner: py.allow_threads(|| CoverageTrackerInner::from_hashes(&xs, radius)),
+            allow_saturating,
+        })
+    }
+
+    /// Empty tracker with room for `n` hashes in every internal structure.
+    #[staticmethod]
+    #[pyo3(signature = (radius, n, *, allow_saturating=false))]
+    fn with_capacity(radius: u32, n: usize, allow_saturating: bool) -> PyResult<Self> {
+        check_width(radius, allow_saturating)?;
+        Ok(Self {
+            inner: CoverageTrackerInner::with_capacity(radius, n),
+            allow_saturating,
+        })
+    }
+
+    /// Sorted numpy uint64 array of the unique hashes, for `from_hashes`.
+    fn export_hashes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_numpy(py, self.inner.export_hashes(), "uint64")
+    }
+
+    /// Every distinct hash stored, as a numpy uint64 array in arbitrary order.
+    fn unique_hashes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_numpy(py, self.inner.unique_hashes_vec(), "uint64")
+    }
+
+    /// Return (hashes, labels) numpy uint64 arrays with dense cluster labels.
+    fn flat_labeling<'py>(&mut self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        let (keys, labels) = self.inner.flat_labeling();
+        Ok((to_numpy(py, keys, "uint64")?, to_numpy(py, labels, "uint64")?))
+    }
+
+    /// Up to `size` (hashes, roots) numpy uint64 arrays from offset `start` in
+    /// insertion order; empty once `start` passes the end. Call with
+    /// start = 0, size, 2 * size, ... to export a large tracker in chunks.
+    fn labeled_chunk<'py>(
+        &mut self,
+        py: Python<'py>,
+        start: usize,
+        size: usize,
+    ) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        let (hashes, roots): (Vec<u64>, Vec<u64>) = self.inner.iter_labeled_from(start).take(size).unzip();
+        Ok((to_numpy(py, hashes, "uint64")?, to_numpy(py, roots, "uint64")?))
+    }
+
+    /// Nearest-rank cluster-size percentiles for each p in [0, 100].
+    fn cluster_size_percentiles(&self, ps: Vec<f64>) -> PyResult<Vec<usize>> {
+        Ok(self.inner.cluster_size_percentiles(&ps)?)
+    }
+
+    /// How many times x was added (0 unless count_occurrences=True and x is stored).
+    fn occurrence_count(&self, x: u64) -> u32 {
+        self.inner.occurrence_count(x)
+    }
+
+    /// The k most often added hashes as (hash, count) tuples, most frequent first.
+    fn most_frequent(&self, k: usize) -> Vec<(u64, u32)> {
+        self.inner.most_frequent(k)
+    }
+
+    /// The k clusters whose closest member is nearest to `x`, as (root, distance)
+    /// tuples sorted by distance. Does not store `x`.
+    fn nearest_clusters(&mut self, x: u64, k: usize) -> Vec<(u64, u32)> {
+        self.inner.nearest_clusters(x, k)
+    }
+
+    /// The k largest clusters as (root, size) tuples, largest first.
+    fn top_clusters(&self, k: usize) -> Vec<(u64, usize)> {
+        self.inner.top_clusters(k)
+    }
+
+    /// Dict mapping nearest-neighbour distance to how many stored hashes have it.
+    fn nearest_neighbor_distance_histogram(&self, py: Python<'_>) -> HashMap<u32, usize> {
+        py.allow_threads(|| self.inner.nearest_neighbor_distance_histogram())
+    }
+
+    /// Number of stored hashes at each Hamming distance 0..=64 from `reference` (65 entries).
+    fn distance_histogram_to(&self, reference: u64) -> Vec<usize> {
+        self.inner.distance_histogram_to(reference).to_vec()
+    }
+
+    /// One-line JSON report: radius, totals, largest cluster, singletons, peak coverage.
+    fn summary_json(&self) -> String {
+        self.inner.summary_json()
+    }
+
+    /// Rebuild the BK-tree from the stored hashes in a balanced order.
+    /// Coverage is unchanged; subsequent queries on a long-run tracker get faster.
+    fn rebuild_tree(&mut self, py: Python<'_>) {
+        py.allow_threads(|| self.inner.rebuild_tree())
+    }
+
+    /// Raw sub-structure sizes for invariant assertions in tests: a dict with
+    /// exact_len, tree_len, uf_len and uf_components.
+    fn debug_state(&self) -> HashMap<&'static str, usize> {
+        let state = self.inner.debug_state();
+        HashMap::from([
+            ("exact_len", state.exact_len),
+            ("tree_len", state.tree_len),
+            ("uf_len", state.uf_len),
+            ("uf_components", state.uf_components),
+        ])
+    }
+
+    /// Coverage count after each add_hash call (requires record_history=True).
+    #[getter]
+    fn coverage_history(&self) -> Vec<usize> {
+        self.inner.coverage_history().to_vec()
+    }
+
+    /// Per-step (total_unique, live_components, monotone_clusters) tuples
+    /// (requires history_mode="full").
+    #[getter]
+    fn history_points(&self) -> Vec<(usize, usize, usize)> {
+        self.inner
+            .history_points()
+            .iter()
+            .map(|p| (p.total_unique, p.live_components, p.monotone_clusters))
+            .collect()
+    }
+
+    /// (hash, was_new, components_after) per add_hash call (requires record_events=True).
+    fn event_log(&self) -> Vec<(u64, bool, usize)> {
+        self.inner
+            .event_log()
+            .iter()
+            .map(|e| (e.hash, e.was_new, e.components_after))
+            .collect()
+    }
+
+    /// Offsets into coverage_history where each episode after the first begins.
+    #[getter]
+    fn episode_starts(&self) -> Vec<usize> {
+        self.inner.episode_starts().to_vec()
+    }
+
+    /// Compare stored hashes and cluster membership with another tracker.
+    fn diff(&mut self, mut other: PyRefMut<'_, CoverageTracker>) -> CoverageDiff {
+        let diff = self.inner.diff(&mut other.inner);
+        CoverageDiff {
+            only_in_self: diff.only_in_self,
+            only_in_other: diff.only_in_other,
+            clusters_only_in_self: diff.clusters_only_in_self,
+            clusters_only_in_other: diff.clusters_only_in_other,
+        }
+    }
+
+    /// Exchange state with another tracker of the same radius.
+    ///
+    /// A background thread can build a tracker and hand it over in one call.
+    fn swap(&mut self, mut other: PyRefMut<'_, CoverageTracker>) -> PyResult<()> {
+        Ok(self.inner.swap(&mut other.inner)?)
+    }
+
+    /// Independent copy of this tracker; changes to either never affect the other.
+    fn fork(&self) -> Self {
         Self {
-            inner: CoverageTrackerInner::new(radius),
+            inner: self.inner.fork(),
+            allow_saturating: self.allow_saturating,
+        }
+    }
+
+    /// Move the built BK-tree out into a new BKTree without copying it.
+    ///
+    /// This tracker is left empty, as after reset(), keeping its configuration.
+    #[pyo3(name = "into_bktree")]
+    fn take_bktree(&mut self) -> BKTree {
+        BKTree {
+            inner: self.inner.take_bktree(),
+            allow_saturating: self.allow_saturating,
         }
     }
 
+    fn reset(&mut self) {
+        self.inner.reset()
+    }
+
+    /// Clear coverage state but keep coverage_history, marking a new episode.
+    fn reset_keep_history(&mut self) {
+        self.inner.reset_keep_history()
+    }
+}
+
+/// Coverage tracker sharded by hash prefix for concurrent ingestion.
+///
+/// Methods release the GIL, so several Python threads can insert at once.
+/// `coverage_count` sums per-shard clusters and never merges across shards.
+#[pyclass(frozen)]
+struct ShardedCoverageTracker {
+    inner: ShardedCoverageTrackerInner,
+}
+
+#[pymethods]
+impl ShardedCoverageTracker {
+    /// Radii above 64 raise ValueError unless `allow_saturating`.
+    #[new]
+    #[pyo3(signature = (radius, num_shards=16, *, allow_saturating=false))]
+    fn new(radius: u32, num_shards: usize, allow_saturating: bool) -> PyResult<Self> {
+        check_width(radius, allow_saturating)?;
+        Ok(Self {
+            inner: ShardedCoverageTrackerInner::new(radius, num_shards),
+        })
+    }
+
     /// Insert a hash. Returns True if the hash was new.
+    fn add_hash(&self, py: Python<'_>, x: u64) -> bool {
+        py.allow_threads(|| self.inner.add_hash(x))
+    }
+
+    #[getter]
+    fn num_shards(&self) -> usize {
+        self.inner.num_shards()
+    }
+
+    #[getter]
+    fn coverage_count(&self, py: Python<'_>) -> usize {
+        py.allow_threads(|| self.inner.coverage_count())
+    }
+
+    #[getter]
+    fn total_unique(&self, py: Python<'_>) -> usize {
+        py.allow_threads(|| self.inner.total_unique())
+    }
+
+    fn reset(&self, py: Python<'_>) {
+        py.allow_threads(|| self.inner.reset())
+    }
+}
+
+/// Coverage tracker over a sliding window of the most recent unique hashes.
+#[pyclass]
+struct WindowedCoverageTracker {
+    inner: WindowedCoverageTrackerInner,
+}
+
+#[pymethods]
+impl WindowedCoverageTracker {
+    /// Radii above 64 raise ValueError unless `allow_saturating`.
+    #[new]
+    #[pyo3(signature = (radius, window, *, allow_saturating=false))]
+    fn new(radius: u32, window: usize, allow_saturating: bool) -> PyResult<Self> {
+        check_width(radius, allow_saturating)?;
+        Ok(Self {
+            inner: WindowedCoverageTrackerInner::new(radius, window),
+        })
+    }
+
+    /// Insert a hash. Returns True if the hash was new to the window.
     fn add_hash(&mut self, x: u64) -> bool {
         self.inner.add_hash(x)
     }
 
+    /// Insert a hash. Returns (was_new, evicted) where evicted is the hash pushed out, if any.
+    fn add_hash_windowed(&mut self, x: u64) -> (bool, Option<u64>) {
+        self.inner.add_hash_windowed(x)
+    }
+
+    #[getter]
+    fn window(&self) -> usize {
+        self.inner.window()
+    }
+
+    #[getter]
+    fn coverage_count(&mut self) -> usize {
+        self.inner.coverage_count()
+    }
+
+    #[getter]
+    fn total_unique(&self) -> usize {
+        self.inner.total_unique()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset()
+    }
+}
+
+// 32-bit fingerprints are stored zero-extended in the u64 structures: the top
+// 32 bits always agree, so distances, radius 32 as "match everything", and
+// coverage counts are exactly those of a native 32-bit implementation.
+
+/// BK-tree for Hamming-distance queries on 32-bit perceptual hashes.
+///
+/// Radii above 32 raise ValueError unless constructed with allow_saturating=True.
+#[pyclass]
+struct BKTree32 {
+    inner: BKTreeInner,
+    allow_saturating: bool,
+}
+
+#[pymethods]
+impl BKTree32 {
+    #[new]
+    #[pyo3(signature = (*, allow_saturating=false))]
+    fn new(allow_saturating: bool) -> Self {
+        Self {
+            inner: BKTreeInner::new(),
+            allow_saturating,
+        }
+    }
+
+    /// Insert a hash. Returns True if new, False if exact duplicate.
+    fn add(&mut self, x: u32) -> bool {
+        self.inner.add(x.into())
+    }
+
+    /// `add` for a 4-byte big-endian digest; raises ValueError on any other length.
+    fn add_bytes(&mut self, b: &[u8]) -> PyResult<bool> {
+        Ok(self.add(u32::from_be_bytes(digest(b)?)))
+    }
+
+    /// Check if any stored hash is within Hamming distance `radius` of `x`.
+    fn any_within(&self, x: u32, radius: u32) -> PyResult<bool> {
+        check_width_bits(radius, 32, self.allow_saturating)?;
+        Ok(self.inner.any_within(x.into(), radius))
+    }
+
+    /// `any_within` for a 4-byte big-endian digest.
+    fn any_within_bytes(&self, b: &[u8], radius: u32) -> PyResult<bool> {
+        self.any_within(u32::from_be_bytes(digest(b)?), radius)
+    }
+
+    /// Return all stored hashes within Hamming distance `radius` of `x`.
+    fn find_all_within(&self, x: u32, radius: u32) -> PyResult<Vec<u32>> {
+        check_width_bits(radius, 32, self.allow_saturating)?;
+        Ok(self
+            .inner
+            .find_all_within(x.into(), radius)
+            .into_iter()
+            .map(|v| v as u32)
+            .collect())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// Coverage tracker for 32-bit perceptual hashes.
+#[pyclass]
+struct CoverageTracker32 {
+    inner: CoverageTrackerInner,
+}
+
+#[pymethods]
+impl CoverageTracker32 {
+    /// Radii above 32 raise ValueError unless `allow_saturating`.
+    #[new]
+    #[pyo3(signature = (radius, *, allow_saturating=false))]
+    fn new(radius: u32, allow_saturating: bool) -> PyResult<Self> {
+        check_width_bits(radius, 32, allow_saturating)?;
+        Ok(Self {
+            inner: CoverageTrackerInner::new(radius),
+        })
+    }
+
+    /// Insert a hash. Returns True if the hash was new.
+    fn add_hash(&mut self, x: u32) -> bool {
+        self.inner.add_hash(x.into())
+    }
+
     #[getter]
     fn coverage_count(&self) -> usize {
         self.inner.coverage_count()
@@ -116,6 +832,76 @@ impl CoverageTracker {
     }
 }
 
+/// Differences between two trackers, returned by `CoverageTracker.diff`.
+#[pyclass(frozen, get_all)]
+struct CoverageDiff {
+    only_in_self: Vec<u64>,
+    only_in_other: Vec<u64>,
+    clusters_only_in_self: Vec<Vec<u64>>,
+    clusters_only_in_other: Vec<Vec<u64>>,
+}
+
+#[pymethods]
+impl CoverageDiff {
+    /// True when the trackers differ in stored hashes or cluster membership.
+    fn __bool__(&self) -> bool {
+        !(self.only_in_self.is_empty()
+            && self.only_in_other.is_empty()
+            && self.clusters_only_in_self.is_empty()
+            && self.clusters_only_in_other.is_empty())
+    }
+}
+
+/// Duplication structure of a batch of hashes, returned by `analyze`.
+#[pyclass(frozen, get_all)]
+struct DedupReport {
+    total: usize,
+    exact_unique: usize,
+    clusters_at_radius: usize,
+    largest_cluster: usize,
+}
+
+#[pymethods]
+impl DedupReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "DedupReport(total={}, exact_unique={}, clusters_at_radius={}, largest_cluster={})",
+            self.total, self.exact_unique, self.clusters_at_radius, self.largest_cluster
+        )
+    }
+}
+
+/// Summarise exact duplicates and clusters of `xs` at `radius`. Releases the GIL.
+#[pyfunction]
+#[pyo3(name = "analyze")]
+fn py_analyze(py: Python<'_>, xs: Vec<u64>, radius: u32) -> DedupReport {
+    let report = py.allow_threads(|| monitor::analyze(&xs, radius));
+    DedupReport {
+        total: report.total,
+        exact_unique: report.exact_unique,
+        clusters_at_radius: report.clusters_at_radius,
+        largest_cluster: report.largest_cluster,
+    }
+}
+
+/// Pairwise Hamming distance matrix of `xs` (n x n). Releases the GIL.
+#[pyfunction]
+#[pyo3(name = "pairwise_hamming")]
+fn py_pairwise_hamming(py: Python<'_>, xs: Vec<u64>) -> Vec<Vec<u32>> {
+    py.allow_threads(|| bktree::pairwise_hamming(&xs))
+}
+
+/// Hamming radius equivalent to similarity fraction `sim` over `bits`-bit
+/// hashes: round((1 - sim) * bits), e.g. 0.95 over 64 bits is 3.
+#[pyfunction]
+#[pyo3(name = "radius_for_similarity", signature = (sim, bits=64))]
+fn py_radius_for_similarity(sim: f64, bits: u32) -> PyResult<u32> {
+    if !(0.0..=1.0).contains(&sim) {
+        return Err(PyValueError::new_err(format!("similarity {sim} is outside [0, 1]")));
+    }
+    Ok(distance::radius_for_similarity(sim, bits))
+}
+
 /// gamecov_core — Rust-accelerated core for gamecov frame coverage monitoring.
 #[pymodule]
 #[pyo3(name = "_gamecov_core")]
@@ -123,5 +909,14 @@ fn gamecov_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<BKTree>()?;
     m.add_class::<UnionFind>()?;
     m.add_class::<CoverageTracker>()?;
+    m.add_class::<ShardedCoverageTracker>()?;
+    m.add_class::<WindowedCoverageTracker>()?;
+    m.add_class::<BKTree32>()?;
+    m.add_class::<CoverageTracker32>()?;
+    m.add_class::<CoverageDiff>()?;
+    m.add_class::<DedupReport>()?;
+    m.add_function(wrap_pyfunction!(py_pairwise_hamming, m)?)?;
+    m.add_function(wrap_pyfunction!(py_analyze, m)?)?;
+    m.add_function(wrap_pyfunction!(py_radius_for_similarity, m)?)?;
     Ok(())
 }
@@ -1,7 +1,150 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
 use crate::bktree::BKTreeInner;
-use crate::unionfind::UnionFindInner;
+use crate::distance::{Distance, Hamming, WeightedHamming};
+use crate::error::GamecovError;
+use crate::exactset::{ExactBackend, ExactSet};
+use crate::persist::{self, Kind};
+use crate::unionfind::{UnionFindInner, UnionStrategy};
+
+/// Time spent inside `add_hash`, split by sub-structure.
+///
+/// Only accumulated when instrumentation is enabled with `with_timing`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimingStats {
+    /// Neighbour queries against the BK-tree.
+    pub query: Duration,
+    /// `make_set` and `union` calls on the union-find.
+    pub union: Duration,
+    /// Inserting the new hash into the BK-tree. Folded into `query` when the
+    /// insertion shares the neighbour query's traversal.
+    pub insert: Duration,
+    /// Number of novel hashes timed.
+    pub samples: u64,
+}
+
+/// Which columns `CoverageTrackerInner` records after every `add_hash` call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HistoryMode {
+    /// Record nothing.
+    #[default]
+    Off,
+    /// Record the live `coverage_count` into `coverage_history`.
+    Live,
+    /// Record `coverage_history` plus a [`HistoryPoint`] per step.
+    Full,
+}
+
+impl std::str::FromStr for HistoryMode {
+    type Err = GamecovError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "live" => Ok(Self::Live),
+            "full" => Ok(Self::Full),
+            _ => Err(GamecovError::InvalidArgument(format!(
+                "unknown history mode {s:?}; expected \"off\", \"live\" or \"full\""
+            ))),
+        }
+    }
+}
+
+/// Coverage columns recorded per `add_hash` call in [`HistoryMode::Full`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HistoryPoint {
+    /// Distinct hashes stored so far; non-decreasing within an episode.
+    pub total_unique: usize,
+    /// Current number of clusters (`coverage_count`). Drops when an insertion
+    /// bridges existing clusters, so this column is not monotone.
+    pub live_components: usize,
+    /// Number of clusters ever founded, i.e. hashes that arrived with no
+    /// neighbour in range. Later merges never lower it, so it is
+    /// non-decreasing within an episode.
+    pub monotone_clusters: usize,
+}
+
+/// One `add_hash` call, as recorded by [`CoverageTrackerInner::with_event_log`]
+/// or returned by [`CoverageTrackerInner::add_hashes_events`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddEvent {
+    pub hash: u64,
+    /// What `add_hash` returned.
+    pub was_new: bool,
+    /// Stored hashes within radius of a new hash; 0 for duplicates.
+    pub neighbor_count: usize,
+    /// Whether the insertion reduced the component count; see `last_add_merged`.
+    pub merged: bool,
+    /// `coverage_count` right after the call.
+    pub components_after: usize,
+}
+
+/// Differences between two trackers' contents, from [`CoverageTrackerInner::diff`].
+///
+/// Hashes and clusters are sorted, so equal inputs give equal reports.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageDiff {
+    /// Hashes stored only in `self`.
+    pub only_in_self: Vec<u64>,
+    /// Hashes stored only in `other`.
+    pub only_in_other: Vec<u64>,
+    /// Clusters of `self` with no cluster of identical membership in `other`.
+    pub clusters_only_in_self: Vec<Vec<u64>>,
+    /// Clusters of `other` with no cluster of identical membership in `self`.
+    pub clusters_only_in_other: Vec<Vec<u64>>,
+}
+
+impl CoverageDiff {
+    /// True if both trackers hold the same hashes partitioned the same way.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty()
+            && self.only_in_other.is_empty()
+            && self.clusters_only_in_self.is_empty()
+            && self.clusters_only_in_other.is_empty()
+    }
+}
+
+/// Duplication structure of a batch of hashes, as computed by [`analyze`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupReport {
+    /// Number of input hashes, duplicates included.
+    pub total: usize,
+    /// Number of distinct hashes.
+    pub exact_unique: usize,
+    /// Number of clusters at the requested radius.
+    pub clusters_at_radius: usize,
+    /// Size of the largest cluster, in distinct hashes.
+    pub largest_cluster: usize,
+}
+
+/// Summarise the duplication structure of `xs` at `radius` in one pass.
+///
+/// Runs a throwaway tracker over the input; useful for triaging a dataset
+/// before committing to a tracker configuration.
+pub fn analyze(xs: &[u64], radius: u32) -> DedupReport {
+    let mut tracker = CoverageTrackerInner::new(radius);
+    for &x in xs {
+        tracker.add_hash(x);
+    }
+    DedupReport {
+        total: xs.len(),
+        exact_unique: tracker.total_unique(),
+        clusters_at_radius: tracker.coverage_count(),
+        largest_cluster: tracker.largest_cluster(),
+    }
+}
+
+/// Weight of the normalized nearest-neighbour distance in `novelty_score`.
+pub const NOVELTY_DISTANCE_WEIGHT: f64 = 0.5;
+/// Weight of the inverse target-cluster size in `novelty_score`.
+pub const NOVELTY_CLUSTER_WEIGHT: f64 = 0.5;
+
+/// Default threshold for [`CoverageTrackerInner::is_over_connected`].
+pub const DEFAULT_OVER_CONNECTED_FRACTION: f64 = 0.5;
+
+/// Default number of recent insertions kept for [`CoverageTrackerInner::recent_new_clusters`].
+pub const DEFAULT_RECENT_WINDOW: usize = 1024;
 
 /// Combined BK-tree + UnionFind coverage tracker.
 ///
@@ -9,114 +152,1539 @@ use crate::unionfind::UnionFindInner;
 /// each new hash is inserted into the BK-tree, all neighbours within
 /// `radius` are found, and the hash is unioned with each neighbour.
 /// Coverage is measured as the number of connected components.
-pub struct CoverageTrackerInner {
-    bktree: BKTreeInner,
+///
+/// Generic over the distance metric, which defaults to plain Hamming.
+#[derive(Clone)]
+pub struct CoverageTrackerInner<D: Distance = Hamming> {
+    bktree: BKTreeInner<D>,
     uf: UnionFindInner,
-    exact: HashSet<u64>,
+    exact: ExactSet,
     radius: u32,
+    history_mode: HistoryMode,
+    /// `coverage_count` after every `add_hash` call, when recording is enabled.
+    history: Vec<usize>,
+    /// Per-step columns, recorded only in `HistoryMode::Full`.
+    history_points: Vec<HistoryPoint>,
+    /// Every insertion in call order, when enabled.
+    events: Option<Vec<AddEvent>>,
+    /// Hashes that started a new cluster since the last reset.
+    clusters_founded: usize,
+    /// Whether each of the last `recent_capacity` insertions started a new cluster.
+    recent_new: VecDeque<bool>,
+    recent_capacity: usize,
+    /// Offsets into `history` where each episode after the first begins.
+    episode_starts: Vec<usize>,
+    /// `Some` only when instrumentation is enabled.
+    timing: Option<TimingStats>,
+    /// Highest `coverage_count` observed since the last full reset.
+    peak_coverage: usize,
+    /// Insertion sequence id of each novel hash, when enabled.
+    sequence: Option<HashMap<u64, u64>>,
+    /// Next sequence id; keeps counting across `reset_keep_history`.
+    next_sequence: u64,
+    /// Times each stored hash was passed to `add_hash`, when enabled.
+    occurrences: Option<HashMap<u64, u32>>,
+    /// Every `(new_hash, neighbour)` pair found within radius, and every pair
+    /// joined by `manual_union`, when enabled.
+    edges: Option<Vec<(u64, u64)>>,
+    /// Distinct pairs in `edges`; counted even when `edges` is off.
+    edge_count: usize,
+    /// Whether the most recent insertion merged existing clusters.
+    last_add_merged: bool,
+    /// Largest-cluster share of unique hashes above which `is_over_connected` fires.
+    over_connected_fraction: f64,
+    /// New hashes within this distance of a stored hash are dropped as duplicates.
+    dedup_radius: u32,
+    /// Sort neighbour results so unions and recorded edges follow value order.
+    deterministic: bool,
+    /// Byte budget enforced by evicting cold clusters, when set.
+    budget: Option<MemoryBudget>,
+}
+
+/// Raw sizes of a tracker's sub-structures, from [`CoverageTrackerInner::debug_state`].
+///
+/// For invariant assertions in tests: after any sequence of public calls the
+/// three lengths are equal and `uf_components` equals `coverage_count`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrackerDebugState {
+    pub exact_len: usize,
+    pub tree_len: usize,
+    pub uf_len: usize,
+    pub uf_components: usize,
+}
+
+/// State behind [`CoverageTrackerInner::set_memory_budget`].
+#[derive(Clone)]
+struct MemoryBudget {
+    bytes: usize,
+    /// Tick of the most recent `add_hash` call naming each stored hash or a
+    /// soft duplicate it absorbed.
+    last_seen: HashMap<u64, u64>,
+    tick: u64,
+    /// Hashes dropped by eviction since the last `reset`.
+    evicted: usize,
+}
+
+/// Every stored hash is registered in the union-find, so lookups of stored
+/// hashes cannot fail.
+const STORED: &str = "stored hashes are registered in the union-find";
+
+/// Removes a just-inserted value from the BK-tree unless disarmed, so an
+/// unwind between insertion and commit leaves no half-added hash behind.
+struct InsertGuard<'a, D: Distance> {
+    tree: &'a mut BKTreeInner<D>,
+    x: u64,
+    armed: bool,
+}
+
+impl<D: Distance> InsertGuard<'_, D> {
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl<D: Distance> Drop for InsertGuard<'_, D> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.tree.remove(self.x);
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Failpoint: make the next `link` calls panic between query and commit.
+    static FAIL_BEFORE_COMMIT: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
 }
 
 impl CoverageTrackerInner {
     pub fn new(radius: u32) -> Self {
+        Self::with_metric(radius, Hamming)
+    }
+
+    /// Create a tracker whose BK-tree, union-find and exact set each hold `n`
+    /// hashes without reallocating.
+    ///
+    /// Pair with `shrink_to_fit` once the stream ends to give back what went unused.
+    pub fn with_capacity(radius: u32, n: usize) -> Self {
+        let mut tracker = Self::from_parts(BKTreeInner::with_capacity(n), radius);
+        tracker.uf.reserve(n);
+        tracker.exact.reserve(n);
+        tracker
+    }
+
+    /// Write the radius and stored hashes in a versioned, checksummed binary format.
+    ///
+    /// Clusters are recomputed by `load`, so only the state that determines
+    /// them is saved; history, timing and other counters are not.
+    pub fn save<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        let mut payload = self.radius.to_le_bytes().to_vec();
+        persist::put_u64s(&mut payload, self.bktree.values().collect::<Vec<_>>().into_iter());
+        persist::write_frame(w, Kind::Tracker, &payload)
+    }
+
+    /// Read a tracker written by `save`, rejecting other format versions and corrupt data.
+    pub fn load<R: std::io::Read>(r: R) -> Result<Self, GamecovError> {
+        let payload = persist::read_frame(r, Kind::Tracker)?;
+        let (radius, rest) = payload
+            .split_first_chunk::<4>()
+            .ok_or_else(|| GamecovError::Format("malformed payload: missing radius".to_string()))?;
+        let mut tree = BKTreeInner::new();
+        for x in persist::get_u64s(rest)? {
+            tree.add(x);
+        }
+        Ok(Self::from_bktree(tree, u32::from_le_bytes(*radius)))
+    }
+
+    /// Count stored hashes at each Hamming distance `0..=64` from `reference`.
+    pub fn distance_histogram_to(&self, reference: u64) -> [usize; 65] {
+        self.bktree.distance_histogram_to(reference)
+    }
+
+    /// Rebuild the BK-tree from the stored hashes in a shuffled order.
+    ///
+    /// A long, slowly drifting stream inserts hashes in near-sorted order and
+    /// can leave the tree deep and unbalanced; this replaces it with one of
+    /// the expected shape of a random order (see `from_slice_balanced`),
+    /// seeded so the result is reproducible. The stored set and clusters are
+    /// unchanged, so the union-find, counters and coverage are left alone.
+    pub fn rebuild_tree(&mut self) {
+        self.bktree = BKTreeInner::from_slice_balanced(&self.export_hashes(), 0);
+    }
+
+    /// Rebuild a tracker from a bare hash set, recomputing clusters at `radius`.
+    ///
+    /// Counterpart of `export_hashes`; `xs` may be in any order and contain
+    /// duplicates. Only the set is restored, not history or counters.
+    pub fn from_hashes(xs: &[u64], radius: u32) -> Self {
+        let mut sorted = xs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let mut tracker = Self::new(radius);
+        tracker.extend_presorted_unique(&sorted);
+        tracker
+    }
+}
+
+impl CoverageTrackerInner<WeightedHamming> {
+    /// Create a tracker whose distance sums `weights[i]` over differing bits `i`.
+    ///
+    /// `radius` is in the same units as the weights.
+    pub fn with_weights(radius: u32, weights: [u32; 64]) -> Self {
+        Self::with_metric(radius, WeightedHamming { weights })
+    }
+}
+
+impl<D: Distance> CoverageTrackerInner<D> {
+    /// Create a tracker that clusters with a custom distance metric.
+    pub fn with_metric(radius: u32, metric: D) -> Self {
+        Self::from_parts(BKTreeInner::with_metric(metric), radius)
+    }
+
+    /// Adopt a prebuilt BK-tree and cluster its contents at `radius`.
+    ///
+    /// Runs one neighbour query per stored hash. The resulting clusters equal
+    /// those from adding the same hashes with `add_hash`, since connectivity
+    /// does not depend on insertion order. Order-dependent counters (peak and
+    /// founded clusters) start at the adopted coverage count.
+    pub fn from_bktree(tree: BKTreeInner<D>, radius: u32) -> Self {
+        let mut tracker = Self::from_parts(tree, radius);
+        for x in tracker.bktree.values() {
+            tracker.exact.insert(x);
+            tracker.uf.make_set(x);
+        }
+        for x in tracker.exact.iter() {
+            for nb in tracker.bktree.find_all_within(x, radius) {
+                tracker.edge_count += usize::from(nb < x);
+                tracker.uf.union(x, nb).expect(STORED);
+            }
+        }
+        tracker.peak_coverage = tracker.coverage_count();
+        tracker.clusters_founded = tracker.coverage_count();
+        tracker
+    }
+
+    /// Consume the tracker and return its BK-tree without copying it.
+    ///
+    /// The inverse of `from_bktree`: the tree holds exactly the stored hashes
+    /// and can serve similarity queries once clustering is finished.
+    pub fn into_bktree(self) -> BKTreeInner<D> {
+        self.bktree
+    }
+
+    /// Move the BK-tree out, leaving this tracker empty as after `reset` with
+    /// its configuration (history mode, dedup radius, exact backend, union
+    /// strategy, optional logs and memory budget) intact.
+    pub fn take_bktree(&mut self) -> BKTreeInner<D>
+    where
+        D: Clone,
+    {
+        let empty = BKTreeInner::with_metric(self.bktree.metric().clone());
+        let tree = std::mem::replace(&mut self.bktree, empty);
+        self.reset();
+        tree
+    }
+
+    fn from_parts(bktree: BKTreeInner<D>, radius: u32) -> Self {
         Self {
-            bktree: BKTreeInner::new(),
+            bktree,
             uf: UnionFindInner::new(),
-            exact: HashSet::new(),
+            exact: ExactSet::default(),
             radius,
+            history_mode: HistoryMode::Off,
+            history: Vec::new(),
+            history_points: Vec::new(),
+            events: None,
+            clusters_founded: 0,
+            recent_new: VecDeque::new(),
+            recent_capacity: DEFAULT_RECENT_WINDOW,
+            episode_starts: Vec::new(),
+            timing: None,
+            peak_coverage: 0,
+            sequence: None,
+            next_sequence: 0,
+            occurrences: None,
+            edges: None,
+            edge_count: 0,
+            last_add_merged: false,
+            over_connected_fraction: DEFAULT_OVER_CONNECTED_FRACTION,
+            dedup_radius: 0,
+            deterministic: false,
+            budget: None,
+        }
+    }
+
+    /// Enable or disable recording `coverage_count` after every `add_hash` call.
+    ///
+    /// Shorthand for `with_history_mode(HistoryMode::Live)` or `Off`.
+    pub fn with_history(self, record: bool) -> Self {
+        self.with_history_mode(if record { HistoryMode::Live } else { HistoryMode::Off })
+    }
+
+    /// Select which columns are recorded after every `add_hash` call.
+    pub fn with_history_mode(mut self, mode: HistoryMode) -> Self {
+        self.history_mode = mode;
+        self
+    }
+
+    /// Enable or disable per-insert timing. When disabled, `add_hash` never reads the clock.
+    pub fn with_timing(mut self, enabled: bool) -> Self {
+        self.timing = enabled.then(TimingStats::default);
+        self
+    }
+
+    /// Enable or disable assigning an increasing sequence id to each novel hash.
+    ///
+    /// Costs one map entry per unique hash.
+    pub fn with_sequence_ids(mut self, enabled: bool) -> Self {
+        self.sequence = enabled.then(HashMap::new);
+        self
+    }
+
+    /// Enable or disable counting how often each stored hash is added.
+    ///
+    /// Costs one map entry per unique hash. Hashes dropped by `dedup_radius`
+    /// are not stored and so are not counted.
+    pub fn with_occurrence_counts(mut self, enabled: bool) -> Self {
+        self.occurrences = enabled.then(HashMap::new);
+        self
+    }
+
+    /// Enable or disable recording every similarity edge found during insertion.
+    ///
+    /// Off by default: the edge list grows with the number of within-radius
+    /// pairs, which can far exceed the number of hashes in dense clusters.
+    pub fn with_edges(mut self, enabled: bool) -> Self {
+        self.edges = enabled.then(Vec::new);
+        self
+    }
+
+    /// Enable or disable logging every insertion as an [`AddEvent`].
+    ///
+    /// Meant for replaying a stream against another implementation and
+    /// finding the first call where the two disagree. The log is never
+    /// trimmed: it costs 24 bytes per `add_hash` call, duplicates included,
+    /// so a long stream can outgrow the tracker itself. Like
+    /// `coverage_history` it survives `reset_keep_history` but not `reset`.
+    pub fn with_event_log(mut self, enabled: bool) -> Self {
+        self.events = enabled.then(Vec::new);
+        self
+    }
+
+    /// Treat new hashes within `dedup_radius` of a stored hash as duplicates.
+    ///
+    /// Such a hash is not stored and `add_hash` returns false, exactly as for
+    /// an exact duplicate, which keeps near-identical frames from inflating
+    /// `total_unique`. Clustering still uses `radius`. Dropped hashes never
+    /// link clusters, so a bridge made only of near-duplicates is lost; keep
+    /// `dedup_radius` well below `radius` to make this rare. The default 0
+    /// adds no extra query.
+    pub fn with_dedup_radius(mut self, dedup_radius: u32) -> Self {
+        self.dedup_radius = dedup_radius;
+        self
+    }
+
+    pub fn dedup_radius(&self) -> u32 {
+        self.dedup_radius
+    }
+
+    /// Make cluster representatives and recorded edges independent of
+    /// `HashMap` iteration order, so identical input gives identical output
+    /// in every run.
+    ///
+    /// Switches the union-find to [`UnionStrategy::DeterministicRoots`] (each
+    /// cluster's root is its smallest hash) and sorts neighbour results before
+    /// unioning. Costs a sort per insertion and loses union by rank, typically
+    /// a few percent on `add_hash`. On a populated tracker the clusters are
+    /// kept and their roots recomputed under the new strategy.
+    pub fn with_deterministic(mut self, enabled: bool) -> Self {
+        let strategy = if enabled {
+            UnionStrategy::DeterministicRoots
+        } else {
+            UnionStrategy::ByRank
+        };
+        self.deterministic = enabled;
+        self.uf.set_strategy(strategy);
+        self
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Select the storage used for exact-duplicate detection.
+    ///
+    /// Clustering results are identical for every backend; see `ExactBackend`
+    /// for when a bitset beats the default `HashSet`. Already-stored hashes
+    /// are carried over. Fails if a bitset universe exceeds `MAX_BITSET_UNIVERSE`.
+    pub fn with_exact_backend(mut self, backend: ExactBackend) -> Result<Self, GamecovError> {
+        let mut exact = ExactSet::new(backend)?;
+        for x in self.exact.iter() {
+            exact.insert(x);
+        }
+        self.exact = exact;
+        Ok(self)
+    }
+
+    /// Keep the last `capacity` insertions for `recent_new_clusters`
+    /// (default [`DEFAULT_RECENT_WINDOW`]); 0 disables tracking.
+    pub fn with_recent_window(mut self, capacity: usize) -> Self {
+        self.recent_capacity = capacity;
+        self.recent_new.truncate(capacity);
+        self
+    }
+
+    /// Set the largest-cluster share above which `is_over_connected` reports true.
+    ///
+    /// Fails with `InvalidArgument` if `fraction` is not in `(0, 1]`.
+    pub fn with_over_connected_fraction(mut self, fraction: f64) -> Result<Self, GamecovError> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(GamecovError::InvalidArgument(format!(
+                "over_connected_fraction {fraction} is outside (0, 1]"
+            )));
         }
+        self.over_connected_fraction = fraction;
+        Ok(self)
     }
 
-    /// Insert a hash. Returns true if the hash was new (not an exact duplicate).
+    /// Insert a hash. Returns true if the hash was new: neither an exact
+    /// duplicate nor within `dedup_radius` of a stored hash.
     pub fn add_hash(&mut self, x: u64) -> bool {
-        if !self.exact.insert(x) {
-            return false; // exact duplicate
+        self.insert(x, false).is_some()
+    }
+
+    /// Insert hashes as the iterator yields them, returning how many were new.
+    ///
+    /// Nothing is buffered, so this composes with readers and generators
+    /// without collecting the input first.
+    pub fn add_from_iter<I: IntoIterator<Item = u64>>(&mut self, iter: I) -> usize {
+        iter.into_iter().filter(|&x| self.add_hash(x)).count()
+    }
+
+    /// Insert every hash in order, returning whether each was new when added.
+    ///
+    /// A repeat of an earlier element of the same batch is reported as not new.
+    pub fn add_hashes_mask(&mut self, xs: &[u64]) -> Vec<bool> {
+        xs.iter().map(|&x| self.add_hash(x)).collect()
+    }
+
+    /// Insert every hash in order, returning what each insertion did.
+    ///
+    /// The batched counterpart of `add_hash` for callers that log topology
+    /// changes: each record says whether the hash was new, how many
+    /// neighbours it linked to and whether it merged clusters.
+    pub fn add_hashes_events(&mut self, xs: &[u64]) -> Vec<AddEvent> {
+        xs.iter()
+            .map(|&x| {
+                let result = self.insert(x, false);
+                self.event_for(x, result)
+            })
+            .collect()
+    }
+
+    /// Insert a hash and return how many stored hashes lie within `radius` of it.
+    ///
+    /// Returns 0 both for exact duplicates and for hashes that start a fresh cluster.
+    pub fn add_hash_neighbors(&mut self, x: u64) -> usize {
+        self.insert(x, false).unwrap_or(0)
+    }
+
+    /// Insert a hash and return true iff it founded a new cluster: it was new
+    /// and had no stored neighbour within `radius` to merge into.
+    ///
+    /// The cheapest reward signal; duplicates and hashes joining existing
+    /// clusters (including bridging ones) return false.
+    pub fn add_hash_increased(&mut self, x: u64) -> bool {
+        self.insert(x, false) == Some(0)
+    }
+
+    /// Insert a hash and return the root of its cluster right after insertion.
+    ///
+    /// Exact duplicates return the root of the cluster they already belong to,
+    /// and hashes dropped by `dedup_radius` the root of the nearest stored hash.
+    /// Roots are only stable until a later insertion merges the cluster, so a
+    /// frame's id identifies the cluster as it stood when the frame arrived.
+    pub fn add_hash_cluster(&mut self, x: u64) -> u64 {
+        self.insert(x, false);
+        let stored = self.stored_for(x);
+        self.uf.find(stored).expect(STORED)
+    }
+
+    /// `x` if it is stored, otherwise the stored hash within `dedup_radius`
+    /// that absorbed it as a soft duplicate.
+    fn stored_for(&self, x: u64) -> u64 {
+        if self.exact.contains(&x) {
+            x
+        } else {
+            self.bktree.nearest(x).expect(STORED).0
+        }
+    }
+
+    /// Insert hashes the caller guarantees are unique and not yet stored,
+    /// skipping the exact-set duplicate check.
+    ///
+    /// `xs` must be strictly increasing (sorted and deduplicated) and disjoint
+    /// from the stored hashes. This is a performance contract, not a memory
+    /// safety one: breaking it is only caught by debug assertions, and in
+    /// release builds leaves clustering and counts wrong, never undefined
+    /// behaviour. Each element is still queried and unioned as in `add_hash`.
+    pub fn extend_presorted_unique(&mut self, xs: &[u64]) {
+        debug_assert!(xs.windows(2).all(|w| w[0] < w[1]), "input is not strictly increasing");
+        for &x in xs {
+            debug_assert!(!self.exact.contains(&x), "hash {x} is already stored");
+            self.insert(x, true);
+        }
+    }
+
+    /// Declare `a` and `b` equivalent, merging their clusters regardless of distance.
+    ///
+    /// Injects domain knowledge the metric cannot see. A hash that is not
+    /// stored yet is added first but without a neighbour query, so it joins
+    /// clusters only through this union and through later insertions that
+    /// find it within radius; it still counts in history, events and the
+    /// other statistics, and one dropped by `dedup_radius` stands for the
+    /// stored hash that absorbed it. Returns true if two clusters were merged,
+    /// in which case `coverage_count` drops by one and the pair is recorded
+    /// as an edge. A memory budget is enforced once, after the union, so
+    /// neither hash can be evicted before it is joined.
+    pub fn manual_union(&mut self, a: u64, b: u64) -> bool {
+        let budget = self.budget.take();
+        for x in [a, b] {
+            if !self.exact.contains(&x) {
+                self.insert_as(x, true, false);
+            }
+        }
+        self.budget = budget;
+        let (a, b) = (self.stored_for(a), self.stored_for(b));
+        let before = self.coverage_count();
+        self.uf.union(a, b).expect(STORED);
+        let merged = self.coverage_count() < before;
+        if merged {
+            self.edge_count += 1;
+            if let Some(edges) = self.edges.as_mut() {
+                edges.push((a, b));
+            }
+        }
+        if let Some(budget) = self.budget.as_mut() {
+            budget.last_seen.insert(a, budget.tick);
+            budget.last_seen.insert(b, budget.tick);
+            budget.tick += 1;
+            self.enforce_memory_budget();
+        }
+        merged
+    }
+
+    /// Shared insertion path. Returns `None` for an exact or soft duplicate,
+    /// otherwise the number of neighbours the new hash was unioned with.
+    /// `known_new` skips the exact-duplicate check for callers that guarantee
+    /// novelty; the `dedup_radius` check still runs.
+    fn insert(&mut self, x: u64, known_new: bool) -> Option<usize> {
+        self.insert_as(x, known_new, true)
+    }
+
+    /// `insert`, optionally storing a new hash without querying or linking
+    /// its neighbours.
+    fn insert_as(&mut self, x: u64, known_new: bool, find_neighbors: bool) -> Option<usize> {
+        let before = self.coverage_count();
+        let result = self.link(x, known_new, find_neighbors);
+        if result == Some(0) {
+            self.clusters_founded += 1;
+        }
+        if self.recent_capacity > 0 {
+            if self.recent_new.len() == self.recent_capacity {
+                self.recent_new.pop_front();
+            }
+            self.recent_new.push_back(result == Some(0));
+        }
+        if let (Some(_), Some(sequence)) = (result, self.sequence.as_mut()) {
+            sequence.insert(x, self.next_sequence);
+            self.next_sequence += 1;
+        }
+        if let Some(occurrences) = self.occurrences.as_mut() {
+            if self.exact.contains(&x) {
+                let count = occurrences.entry(x).or_insert(0);
+                *count = count.saturating_add(1);
+            }
+        }
+        let coverage = self.coverage_count();
+        self.last_add_merged = coverage < before + usize::from(result.is_some());
+        self.peak_coverage = self.peak_coverage.max(coverage);
+        if self.history_mode != HistoryMode::Off {
+            self.history.push(coverage);
+        }
+        if self.history_mode == HistoryMode::Full {
+            self.history_points.push(HistoryPoint {
+                total_unique: self.total_unique(),
+                live_components: coverage,
+                monotone_clusters: self.clusters_founded,
+            });
+        }
+        let event = self.event_for(x, result);
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+        if self.budget.is_some() {
+            // a soft duplicate keeps the hash that absorbed it warm
+            let seen = self.stored_for(x);
+            let budget = self.budget.as_mut().expect("budget is set");
+            budget.last_seen.insert(seen, budget.tick);
+            budget.tick += 1;
+            self.enforce_memory_budget();
+        }
+        result
+    }
+
+    /// Describe the insertion of `x` that just returned `result`.
+    fn event_for(&self, x: u64, result: Option<usize>) -> AddEvent {
+        AddEvent {
+            hash: x,
+            was_new: result.is_some(),
+            neighbor_count: result.unwrap_or(0),
+            merged: self.last_add_merged,
+            components_after: self.coverage_count(),
+        }
+    }
+
+    /// Link a new hash into the BK-tree, exact set and union-find.
+    ///
+    /// Transactional: the steps that can fail (neighbour query and root
+    /// lookups) run before the exact set or union-find change, and the BK-tree
+    /// insertion is rolled back if they unwind. The commit step only touches
+    /// the exact set and union-find and cannot fail. Without `find_neighbors`
+    /// the hash is stored as a singleton.
+    fn link(&mut self, x: u64, known_new: bool, find_neighbors: bool) -> Option<usize> {
+        if !known_new && self.exact.contains(&x) {
+            return None; // exact duplicate
+        }
+        if self.is_soft_duplicate(x) {
+            return None;
+        }
+        let mut clock = self.timing.is_some().then(Instant::now);
+
+        let mut neighbors: Vec<u64> = if !find_neighbors || (self.radius == 0 && D::IS_HAMMING) {
+            // Pure dedup: distinct hashes are never within distance 0 of each other,
+            // so every new hash is its own component and coverage_count == total_unique.
+            self.bktree.add(x);
+            self.lap(&mut clock, |t| &mut t.insert);
+            Vec::new()
+        } else if self.radius == 1 && D::IS_HAMMING {
+            // 64 set lookups beat a tree traversal once the tree is large
+            let neighbors = (0..64)
+                .map(|bit| x ^ (1u64 << bit))
+                .filter(|nb| self.exact.contains(nb))
+                .collect();
+            self.lap(&mut clock, |t| &mut t.query);
+            self.bktree.add(x);
+            self.lap(&mut clock, |t| &mut t.insert);
+            neighbors
+        } else {
+            // one traversal serves both the neighbour query and the insertion
+            let (_, neighbors) = self.bktree.add_and_find_within(x, self.radius);
+            self.lap(&mut clock, |t| &mut t.query);
+            neighbors
+        };
+
+        if self.deterministic {
+            neighbors.sort_unstable();
         }
 
-        let neighbors = self.bktree.find_all_within(x, self.radius);
+        let guard = InsertGuard {
+            tree: &mut self.bktree,
+            x,
+            armed: true,
+        };
+        // Union once per distinct neighbouring cluster rather than once per neighbour
+        let mut roots: Vec<u64> = neighbors.iter().map(|&nb| self.uf.find(nb).expect(STORED)).collect();
+        roots.sort_unstable();
+        roots.dedup();
+        #[cfg(test)]
+        if FAIL_BEFORE_COMMIT.with(std::cell::Cell::get) {
+            panic!("injected failure before commit");
+        }
+        guard.disarm();
 
+        self.exact.insert(x);
+        self.edge_count += neighbors.len();
+        if let Some(edges) = self.edges.as_mut() {
+            edges.extend(neighbors.iter().map(|&nb| (x, nb)));
+        }
         self.uf.make_set(x);
-        for nb in &neighbors {
-            self.uf.union(x, *nb);
+        for root in roots {
+            self.uf.union(x, root).expect(STORED);
+        }
+        self.lap(&mut clock, |t| &mut t.union);
+        self.count_sample();
+        Some(neighbors.len())
+    }
+
+    /// True if `x` lies within a nonzero `dedup_radius` of a stored hash.
+    fn is_soft_duplicate(&self, x: u64) -> bool {
+        self.dedup_radius > 0 && self.bktree.any_within(x, self.dedup_radius)
+    }
+
+    fn count_sample(&mut self) {
+        if let Some(stats) = self.timing.as_mut() {
+            stats.samples += 1;
+        }
+    }
+
+    /// Charge the time since `clock` to one timing bucket and restart the clock.
+    fn lap(&mut self, clock: &mut Option<Instant>, bucket: fn(&mut TimingStats) -> &mut Duration) {
+        if let (Some(start), Some(stats)) = (clock.as_mut(), self.timing.as_mut()) {
+            let now = Instant::now();
+            *bucket(stats) += now - *start;
+            *start = now;
+        }
+    }
+
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    /// Raise the clustering radius to `new_radius`, merging clusters in place.
+    ///
+    /// A larger radius only adds edges, so clusters can merge but never split:
+    /// each stored hash is re-queried for neighbours in the new distance band
+    /// and unioned, keeping the existing union-find. Decreasing the radius can
+    /// split clusters and needs a full rebuild from the stored hashes instead.
+    ///
+    /// Fails with `RadiusDecrease`, changing nothing, if `new_radius` is
+    /// smaller than the current radius.
+    pub fn increase_radius(&mut self, new_radius: u32) -> Result<(), GamecovError> {
+        if new_radius < self.radius {
+            return Err(GamecovError::RadiusDecrease {
+                current: self.radius,
+                requested: new_radius,
+            });
+        }
+        if new_radius == self.radius {
+            return Ok(());
         }
+        let lo = self.radius + 1;
+        let mut xs: Vec<u64> = self.exact.iter().collect();
+        if self.deterministic {
+            xs.sort_unstable();
+        }
+        for x in xs {
+            let mut band = self.bktree.find_in_band(x, lo, new_radius);
+            if self.deterministic {
+                band.sort_unstable();
+            }
+            for nb in band {
+                // each new pair is seen from both ends; record it once
+                self.edge_count += usize::from(nb < x);
+                if let Some(edges) = self.edges.as_mut().filter(|_| nb < x) {
+                    edges.push((x, nb));
+                }
+                self.uf.union(x, nb).expect(STORED);
+            }
+        }
+        self.radius = new_radius;
+        Ok(())
+    }
+
+    /// Check whether inserting `x` would merge two or more existing clusters.
+    ///
+    /// Runs the same neighbour query as `add_hash` but leaves all state untouched.
+    /// Exact duplicates never bridge.
+    pub fn would_bridge(&self, x: u64) -> bool {
+        if self.exact.contains(&x) || self.is_soft_duplicate(x) {
+            return false;
+        }
+        let mut first_root = None;
+        for nb in self.bktree.find_all_within(x, self.radius) {
+            let root = self.uf.root_of(nb);
+            match first_root {
+                None => first_root = Some(root),
+                Some(r) if r != root => return true,
+                Some(_) => {}
+            }
+        }
+        false
+    }
+
+    /// Stored hashes within `radius` of `x`, each paired with its cluster root.
+    ///
+    /// Does not insert `x`; if `x` is already stored it is reported too.
+    /// `radius` may differ from the tracker's clustering radius.
+    pub fn query_context(&mut self, x: u64, radius: u32) -> Vec<(u64, u64)> {
+        self.bktree
+            .find_all_within(x, radius)
+            .into_iter()
+            .map(|nb| (nb, self.uf.find(nb).expect(STORED)))
+            .collect()
+    }
 
-        self.bktree.add(x);
-        true
+    /// Query-only novelty of `x` in `[0, 1]`, for use as a reward signal.
+    ///
+    /// `NOVELTY_DISTANCE_WEIGHT * d / m + NOVELTY_CLUSTER_WEIGHT / (1 + s)`,
+    /// where `m` is the metric's `max_distance` (64 for Hamming), `d` is the
+    /// distance to the nearest stored hash (`m` when empty) and `s` is the size of the cluster `x` would land in: its own cluster
+    /// if stored, otherwise the combined size of the clusters within radius
+    /// (0 if it would start a new one). Leaves coverage state unchanged;
+    /// `&mut` is only for union-find path compression.
+    pub fn novelty_score(&mut self, x: u64) -> f64 {
+        let max = self.bktree.metric().max_distance();
+        let d = self.bktree.nearest(x).map_or(max, |(_, d)| d.min(max));
+        let size = if self.exact.contains(&x) {
+            self.uf.component_size(x).expect(STORED)
+        } else {
+            let mut roots: Vec<u64> = self
+                .bktree
+                .find_all_within(x, self.radius)
+                .into_iter()
+                .map(|nb| self.uf.find(nb).expect(STORED))
+                .collect();
+            roots.sort_unstable();
+            roots.dedup();
+            roots
+                .into_iter()
+                .map(|r| self.uf.component_size(r).expect(STORED))
+                .sum()
+        };
+        NOVELTY_DISTANCE_WEIGHT * f64::from(d) / f64::from(max.max(1)) + NOVELTY_CLUSTER_WEIGHT / (1.0 + size as f64)
     }
 
     pub fn coverage_count(&self) -> usize {
         self.uf.component_count()
     }
 
+    /// Number of clusters the stored hashes would form at `radius`, leaving
+    /// the tracker untouched.
+    ///
+    /// Clusters into a scratch union-find using the existing BK-tree. A
+    /// larger radius starts from a copy of the current clusters and only
+    /// queries the extra distance band; a smaller one re-queries from
+    /// scratch. Either way it costs one tree query per stored hash.
+    pub fn coverage_count_at(&self, radius: u32) -> usize {
+        if radius == self.radius {
+            return self.coverage_count();
+        }
+        let (mut uf, lo) = if radius > self.radius {
+            (self.uf.clone(), self.radius + 1)
+        } else {
+            let mut uf = UnionFindInner::new();
+            for x in self.exact.iter() {
+                uf.make_set(x);
+            }
+            (uf, 0)
+        };
+        for x in self.exact.iter() {
+            for nb in self.bktree.find_in_band(x, lo, radius) {
+                if nb < x {
+                    uf.union(x, nb).expect(STORED);
+                }
+            }
+        }
+        uf.component_count()
+    }
+
+    /// Union-find `union` calls made since the last reset.
+    ///
+    /// Compared with `union_merges`, shows how much union work dense
+    /// neighbourhoods cause without changing any cluster.
+    pub fn union_calls(&self) -> u64 {
+        self.uf.union_calls()
+    }
+
+    /// Union-find `union` calls since the last reset that merged two clusters.
+    pub fn union_merges(&self) -> u64 {
+        self.uf.union_merges()
+    }
+
+    /// Number of clusters with at least `min` hashes.
+    ///
+    /// `min = 2` ignores isolated frames, giving a steadier curve than
+    /// `coverage_count`; `min <= 1` equals `coverage_count`. Costs one pass
+    /// over the union-find.
+    pub fn coverage_count_min_size(&self, min: usize) -> usize {
+        self.uf.components_at_least(min)
+    }
+
     pub fn total_unique(&self) -> usize {
         self.exact.len()
     }
 
-    pub fn reset(&mut self) {
-        self.bktree = BKTreeInner::new();
-        self.uf = UnionFindInner::new();
-        self.exact.clear();
+    /// Release unused capacity in the BK-tree, union-find, exact set and
+    /// per-hash maps, lowering `memory_bytes` to roughly what the stored
+    /// hashes need. BK-tree tombstones, if any, are kept.
+    pub fn shrink_to_fit(&mut self) {
+        self.bktree.shrink_to_fit();
+        self.uf.shrink_to_fit();
+        self.exact.shrink_to_fit();
+        if let Some(sequence) = self.sequence.as_mut() {
+            sequence.shrink_to_fit();
+        }
+        if let Some(occurrences) = self.occurrences.as_mut() {
+            occurrences.shrink_to_fit();
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Approximate heap footprint in bytes of the stored hashes: BK-tree,
+    /// union-find, exact set and per-hash maps, counting allocated capacity.
+    ///
+    /// O(1). Append-only logs (history, events, edges) are not included.
+    pub fn memory_bytes(&self) -> usize {
+        let table = |capacity: usize, entry: usize| capacity * (entry + 1);
+        let (seq_entry, count_entry) = (std::mem::size_of::<(u64, u64)>(), std::mem::size_of::<(u64, u32)>());
+        std::mem::size_of::<Self>()
+            + self.bktree.memory_bytes()
+            + self.uf.memory_bytes()
+            + self.exact.memory_bytes()
+            + self.sequence.as_ref().map_or(0, |m| table(m.capacity(), seq_entry))
+            + self
+                .occurrences
+                .as_ref()
+                .map_or(0, |m| table(m.capacity(), count_entry))
+            + self
+                .budget
+                .as_ref()
+                .map_or(0, |b| table(b.last_seen.capacity(), seq_entry))
+    }
 
-    #[test]
-    fn test_empty_tracker() {
-        let tracker = CoverageTrackerInner::new(5);
-        assert_eq!(tracker.coverage_count(), 0);
-        assert_eq!(tracker.total_unique(), 0);
+    /// Keep `memory_bytes` under `bytes` by evicting whole clusters.
+    ///
+    /// The footprint is checked after every `add_hash`. Once over, the
+    /// coldest clusters (those whose most recently added hash is oldest,
+    /// smaller first on ties) are removed from the BK-tree, union-find and
+    /// exact set until the footprint is about half the budget. The low-water
+    /// mark keeps the O(n) eviction pass from running again as soon as the
+    /// next insertion grows a container. The cluster holding the most
+    /// recently added hash is never evicted, so a budget too small for it
+    /// alone is exceeded.
+    ///
+    /// Coverage then reflects a bounded, recently active subset of the stream
+    /// rather than everything seen, and a revisited evicted region counts as
+    /// new again. Monotone counters (`peak_coverage`, founded clusters,
+    /// `edge_count`) and recorded history are not rewound.
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        match self.budget.as_mut() {
+            Some(budget) => budget.bytes = bytes,
+            None => {
+                self.budget = Some(MemoryBudget {
+                    bytes,
+                    last_seen: self.exact.iter().map(|x| (x, 0)).collect(),
+                    tick: 1,
+                    evicted: 0,
+                });
+            }
+        }
+        self.enforce_memory_budget();
     }
 
-    #[test]
-    fn test_single_hash() {
-        let mut tracker = CoverageTrackerInner::new(5);
-        assert!(tracker.add_hash(42));
-        assert_eq!(tracker.coverage_count(), 1);
-        assert_eq!(tracker.total_unique(), 1);
+    /// The budget set by `set_memory_budget`, if any.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.budget.as_ref().map(|b| b.bytes)
     }
 
-    #[test]
-    fn test_exact_duplicate() {
-        let mut tracker = CoverageTrackerInner::new(5);
-        assert!(tracker.add_hash(42));
-        assert!(!tracker.add_hash(42)); // duplicate
-        assert_eq!(tracker.total_unique(), 1);
-        assert_eq!(tracker.coverage_count(), 1);
+    /// Hashes evicted to stay under the memory budget since the last `reset`.
+    pub fn evicted_count(&self) -> usize {
+        self.budget.as_ref().map_or(0, |b| b.evicted)
     }
 
-    #[test]
-    fn test_nearby_hashes_merge() {
-        let mut tracker = CoverageTrackerInner::new(2);
-        // 0b0000 and 0b0001 have Hamming distance 1 (<= radius 2)
-        tracker.add_hash(0b0000);
-        tracker.add_hash(0b0001);
-        assert_eq!(tracker.total_unique(), 2);
-        assert_eq!(tracker.coverage_count(), 1); // merged into one component
+    /// Evict cold clusters if the footprint exceeds the budget.
+    fn enforce_memory_budget(&mut self) {
+        let Some(limit) = self.memory_budget() else {
+            return;
+        };
+        let bytes = self.memory_bytes();
+        if bytes > limit {
+            self.evict_cold_clusters(bytes - limit / 2, bytes);
+        }
     }
 
-    #[test]
-    fn test_distant_hashes_separate() {
-        let mut tracker = CoverageTrackerInner::new(1);
-        // 0b0000 and 0b0111 have Hamming distance 3 (> radius 1)
-        tracker.add_hash(0b0000);
-        tracker.add_hash(0b0111);
-        assert_eq!(tracker.total_unique(), 2);
-        assert_eq!(tracker.coverage_count(), 2); // separate components
+    /// Remove the coldest clusters until roughly `excess` of `bytes` is freed.
+    fn evict_cold_clusters(&mut self, excess: usize, bytes: usize) {
+        let budget = self.budget.as_mut().expect("budget is set");
+        let mut clusters: HashMap<u64, (u64, usize)> = HashMap::new();
+        for x in self.exact.iter() {
+            let root = self.uf.find(x).expect(STORED);
+            let seen = budget.last_seen.get(&x).copied().unwrap_or(0);
+            let (last, size) = clusters.entry(root).or_insert((0, 0));
+            *last = (*last).max(seen);
+            *size += 1;
+        }
+        // coldest first, smaller first on ties; the hottest cluster always stays
+        let mut order: Vec<(u64, usize, u64)> = clusters
+            .into_iter()
+            .map(|(root, (last, size))| (last, size, root))
+            .collect();
+        order.sort_unstable();
+        order.pop();
+        let quota = excess.div_ceil((bytes / self.exact.len().max(1)).max(1));
+        let mut victims = HashSet::new();
+        let mut taken = 0;
+        for (_, size, root) in order {
+            if taken >= quota {
+                break;
+            }
+            victims.insert(root);
+            taken += size;
+        }
+        if victims.is_empty() {
+            return;
+        }
+
+        let mut dropped = Vec::with_capacity(taken);
+        let mut kept = Vec::with_capacity(self.exact.len() - taken);
+        for x in self.exact.iter() {
+            let root = self.uf.find(x).expect(STORED);
+            if victims.contains(&root) {
+                dropped.push(x);
+            } else {
+                kept.push((x, root));
+            }
+        }
+        for &x in &dropped {
+            self.bktree.remove(x);
+            self.uf.remove(x);
+            self.exact.remove(x);
+            budget.last_seen.remove(&x);
+            if let Some(sequence) = self.sequence.as_mut() {
+                sequence.remove(&x);
+            }
+            if let Some(occurrences) = self.occurrences.as_mut() {
+                occurrences.remove(&x);
+            }
+        }
+        if let Some(edges) = self.edges.as_mut() {
+            let gone: HashSet<u64> = dropped.iter().copied().collect();
+            edges.retain(|(a, b)| !gone.contains(a) && !gone.contains(b));
+        }
+        // survivors keep their clusters: each is re-linked to its old root
+        self.uf.rebuild(kept);
+        self.bktree.compact();
+        self.exact.shrink_to_fit();
+        budget.last_seen.shrink_to_fit();
+        if let Some(sequence) = self.sequence.as_mut() {
+            sequence.shrink_to_fit();
+        }
+        if let Some(occurrences) = self.occurrences.as_mut() {
+            occurrences.shrink_to_fit();
+        }
+        budget.evicted += dropped.len();
     }
 
-    #[test]
-    fn test_bridging_reduces_components() {
-        let mut tracker = CoverageTrackerInner::new(1);
-        // A: 0b0000, B: 0b0011 (distance 2 from A, separate)
-        // C: 0b0001 (distance 1 from A, distance 1 from B -> bridges them)
-        tracker.add_hash(0b0000);
-        tracker.add_hash(0b0011);
-        assert_eq!(tracker.coverage_count(), 2);
+    /// Sizes of the exact set, BK-tree and union-find, read directly rather
+    /// than through the coverage getters. O(1).
+    pub fn debug_state(&self) -> TrackerDebugState {
+        TrackerDebugState {
+            exact_len: self.exact.len(),
+            tree_len: self.bktree.len(),
+            uf_len: self.uf.len(),
+            uf_components: self.uf.component_count(),
+        }
+    }
 
-        tracker.add_hash(0b0001); // bridges A and B
-        assert_eq!(tracker.coverage_count(), 1);
+    /// Verify invariants of the tracker and its sub-structures in O(n).
+    ///
+    /// Beyond the BK-tree and union-find checks, every unique hash must be
+    /// stored exactly once in each of the exact set, BK-tree, and union-find.
+    pub fn debug_check_invariants(&self) -> Result<(), String> {
+        self.bktree.debug_check_invariants()?;
+        self.uf.debug_check_invariants()?;
+        if self.coverage_count() != self.uf.component_count() {
+            return Err("coverage_count diverges from union-find component count".to_string());
+        }
+        let unique = self.total_unique();
+        if self.bktree.len() != unique || self.uf.len() != unique {
+            return Err(format!(
+                "{unique} unique hashes but {} in BK-tree and {} in union-find",
+                self.bktree.len(),
+                self.uf.len()
+            ));
+        }
+        if let Some(x) = self.exact.iter().find(|&x| self.uf.root_of(x).is_none()) {
+            return Err(format!("hash {x} missing from union-find"));
+        }
+        Ok(())
+    }
+
+    /// Every distinct hash stored, in arbitrary order, borrowed from the exact set.
+    pub fn unique_hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.exact.iter()
+    }
+
+    /// `unique_hashes` collected into a Vec.
+    pub fn unique_hashes_vec(&self) -> Vec<u64> {
+        self.unique_hashes().collect()
+    }
+
+    /// Sorted copy of the unique-hash set: a lightweight checkpoint that
+    /// `from_hashes` turns back into an equivalent tracker.
+    pub fn export_hashes(&self) -> Vec<u64> {
+        let mut hashes = self.unique_hashes_vec();
+        hashes.sort_unstable();
+        hashes
+    }
+
+    /// Return `(hashes, labels)` with dense cluster labels `0..coverage_count`.
+    pub fn flat_labeling(&mut self) -> (Vec<u64>, Vec<u64>) {
+        self.uf.flat_labeling()
+    }
+
+    /// Root hash of every cluster.
+    pub fn roots(&self) -> impl Iterator<Item = u64> + '_ {
+        self.uf.roots()
+    }
+
+    /// Lazily yield `(hash, cluster root)` for every stored hash, for
+    /// streaming export without building a map. Roots are found as it goes.
+    pub fn iter_labeled(&mut self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.uf.iter_labeled()
+    }
+
+    /// `iter_labeled` from the `start`th hash in insertion order, so a large
+    /// export can be taken in chunks. Offsets stay valid while no hashes are
+    /// evicted or the tracker reset; new hashes only append.
+    pub fn iter_labeled_from(&mut self, start: usize) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.uf.iter_labeled_from(start)
+    }
+
+    /// Lazily yield the hashes in `x`'s cluster, or `KeyNotFound` if `x` is not stored.
+    pub fn component_iter(&mut self, x: u64) -> Result<impl Iterator<Item = u64> + '_, GamecovError> {
+        self.uf.component_iter(x)
+    }
+
+    /// Compare stored hashes and cluster membership with another tracker.
+    ///
+    /// Takes `&mut` on both sides only for union-find path compression.
+    pub fn diff<E: Distance>(&mut self, other: &mut CoverageTrackerInner<E>) -> CoverageDiff {
+        let sorted = |mut v: Vec<u64>| {
+            v.sort_unstable();
+            v
+        };
+        let only_in_self = sorted(self.exact.iter().filter(|x| !other.exact.contains(x)).collect());
+        let only_in_other = sorted(other.exact.iter().filter(|x| !self.exact.contains(x)).collect());
+        let ours = self.clusters();
+        let theirs = other.clusters();
+        let only = |a: &[Vec<u64>], b: &[Vec<u64>]| {
+            let b: HashSet<&Vec<u64>> = b.iter().collect();
+            a.iter().filter(|c| !b.contains(c)).cloned().collect()
+        };
+        CoverageDiff {
+            only_in_self,
+            only_in_other,
+            clusters_only_in_self: only(&ours, &theirs),
+            clusters_only_in_other: only(&theirs, &ours),
+        }
+    }
+
+    /// Every cluster as a sorted member list, clusters ordered by first member.
+    fn clusters(&mut self) -> Vec<Vec<u64>> {
+        let (keys, labels) = self.uf.flat_labeling();
+        let mut clusters = vec![Vec::new(); self.coverage_count()];
+        for (key, label) in keys.into_iter().zip(labels) {
+            clusters[label as usize].push(key);
+        }
+        for cluster in &mut clusters {
+            cluster.sort_unstable();
+        }
+        clusters.sort_unstable();
+        clusters
+    }
+
+    /// The `k` clusters whose closest member is nearest to `x`, as
+    /// `(root, distance)` sorted by distance then root.
+    ///
+    /// One branch-and-bound BK-tree query, with hits mapped to their cluster
+    /// root and only the best distance per root kept. Answers "which existing
+    /// behaviours is this frame closest to" without storing `x`.
+    pub fn nearest_clusters(&mut self, x: u64, k: usize) -> Vec<(u64, u32)> {
+        let uf = &mut self.uf;
+        self.bktree.nearest_groups(x, k, |v| uf.find(v).expect(STORED))
+    }
+
+    /// The `k` largest clusters as `(root, size)`, largest first.
+    pub fn top_clusters(&self, k: usize) -> Vec<(u64, usize)> {
+        self.uf.top_clusters(k)
+    }
+
+    /// Lazily yield every cluster as `(root, size)`, largest first.
+    ///
+    /// Same order as `top_clusters`, but items are popped from a heap on
+    /// demand, so paging through the first few clusters of millions is cheap.
+    pub fn clusters_by_size(&self) -> impl Iterator<Item = (u64, usize)> {
+        self.uf.clusters_by_size()
+    }
+
+    /// Shannon entropy (bits) of the cluster-size distribution, p_i = size_i / total_unique.
+    ///
+    /// Many small, even clusters score high; one dominant cluster scores near 0.
+    /// Returns 0.0 when empty.
+    pub fn coverage_entropy(&self) -> f64 {
+        let total = self.total_unique() as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.uf
+            .component_size_histogram()
+            .into_iter()
+            .map(|(size, count)| {
+                let p = size as f64 / total;
+                -(count as f64) * p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Map from each stored hash's distance to its nearest other hash to the
+    /// number of hashes with that distance. Costs one tree query per hash.
+    pub fn nearest_neighbor_distance_histogram(&self) -> HashMap<u32, usize> {
+        self.bktree.nearest_neighbor_distance_histogram()
+    }
+
+    /// Cluster-size percentiles (nearest rank) for each `p` in `[0, 100]`.
+    ///
+    /// Computed from the size histogram, so it sorts distinct sizes rather than
+    /// every cluster. Returns 0 for each `p` when the tracker is empty.
+    pub fn cluster_size_percentiles(&self, ps: &[f64]) -> Result<Vec<usize>, GamecovError> {
+        if let Some(p) = ps.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+            return Err(GamecovError::InvalidArgument(format!(
+                "percentile {p} is outside [0, 100]"
+            )));
+        }
+        let mut hist: Vec<(usize, usize)> = self.uf.component_size_histogram().into_iter().collect();
+        hist.sort_unstable();
+        let n = self.coverage_count();
+        Ok(ps
+            .iter()
+            .map(|&p| {
+                let rank = ((p / 100.0 * n as f64).ceil() as usize).max(1);
+                let mut seen = 0;
+                hist.iter()
+                    .find(|&&(_, count)| {
+                        seen += count;
+                        seen >= rank
+                    })
+                    .map_or(0, |&(size, _)| size)
+            })
+            .collect())
+    }
+
+    /// Size of the largest cluster (0 when empty).
+    pub fn largest_cluster(&self) -> usize {
+        self.uf.component_sizes().max().unwrap_or(0)
+    }
+
+    /// Share of unique hashes held by the largest cluster (0.0 when empty).
+    pub fn largest_cluster_fraction(&self) -> f64 {
+        match self.total_unique() {
+            0 => 0.0,
+            total => self.largest_cluster() as f64 / total as f64,
+        }
+    }
+
+    pub fn over_connected_fraction(&self) -> f64 {
+        self.over_connected_fraction
+    }
+
+    /// True when the largest cluster holds more than `over_connected_fraction`
+    /// of all unique hashes: the radius is likely too large and coverage is
+    /// collapsing towards a single cluster.
+    pub fn is_over_connected(&self) -> bool {
+        self.largest_cluster_fraction() > self.over_connected_fraction
+    }
+
+    /// Number of clusters holding a single hash.
+    pub fn singletons(&self) -> usize {
+        self.coverage_count() - self.uf.components_at_least(2)
+    }
+
+    /// Internal insertion counter value assigned to `x` when it was first added.
+    ///
+    /// `None` if `x` is not stored or sequence ids are disabled.
+    pub fn sequence_of(&self, x: u64) -> Option<u64> {
+        self.sequence.as_ref()?.get(&x).copied()
+    }
+
+    /// How many times `x` was added, counting the first insertion.
+    ///
+    /// 0 if `x` is not stored or occurrence counts are disabled.
+    pub fn occurrence_count(&self, x: u64) -> u32 {
+        self.occurrences.as_ref().and_then(|o| o.get(&x).copied()).unwrap_or(0)
+    }
+
+    /// The `k` most often added hashes as `(hash, count)`, most frequent first
+    /// and ties by ascending hash. Empty if occurrence counts are disabled.
+    pub fn most_frequent(&self, k: usize) -> Vec<(u64, u32)> {
+        let Some(occurrences) = self.occurrences.as_ref() else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        let by_count = |a: &(u64, u32), b: &(u64, u32)| b.1.cmp(&a.1).then(a.0.cmp(&b.0));
+        let mut counts: Vec<(u64, u32)> = occurrences.iter().map(|(&x, &n)| (x, n)).collect();
+        if k < counts.len() {
+            counts.select_nth_unstable_by(k - 1, by_count);
+            counts.truncate(k);
+        }
+        counts.sort_unstable_by(by_count);
+        counts
+    }
+
+    /// Recorded `(new_hash, neighbour)` edges in discovery order.
+    ///
+    /// Together with the stored hashes this is the full similarity graph the
+    /// union-find was built from, manual unions included. Empty unless enabled with `with_edges`.
+    pub fn edges(&self) -> &[(u64, u64)] {
+        self.edges.as_deref().unwrap_or_default()
+    }
+
+    /// Number of distinct pairs of stored hashes within the radius of each
+    /// other, plus the pairs whose clusters `manual_union` merged.
+    ///
+    /// Unlike `coverage_count` this never decreases as hashes are added, since
+    /// merges remove components but not edges. Equals `edges().len()` when edge
+    /// recording is enabled, without needing to store the pairs.
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    /// True if the most recent `add_hash` reduced the component count, i.e.
+    /// `components_after < components_before + was_new`: a new hash that
+    /// joined an existing cluster or bridged several. A new singleton or an
+    /// exact duplicate is not a merge.
+    pub fn last_add_merged(&self) -> bool {
+        self.last_add_merged
+    }
+
+    /// New clusters started by the last `window` insertions, exact duplicates
+    /// included. A run of zeros means the stream has stopped finding new states.
+    ///
+    /// Only the last `with_recent_window` insertions are kept, so larger
+    /// windows are clamped to that. O(window).
+    pub fn recent_new_clusters(&self, window: usize) -> usize {
+        self.recent_new.iter().rev().take(window).filter(|&&new| new).count()
+    }
+
+    /// Highest `coverage_count` observed; merges can later lower the live count.
+    pub fn peak_coverage(&self) -> usize {
+        self.peak_coverage
+    }
+
+    /// One-line JSON report of the tracker's headline numbers, for run logs.
+    ///
+    /// This is a summary, not a reloadable snapshot of the tracker.
+    pub fn summary_json(&self) -> String {
+        format!(
+            "{{\"radius\":{},\"total_unique\":{},\"coverage_count\":{},\"largest_cluster\":{},\"singletons\":{},\"peak_coverage\":{}}}",
+            self.radius,
+            self.total_unique(),
+            self.coverage_count(),
+            self.largest_cluster(),
+            self.singletons(),
+            self.peak_coverage
+        )
+    }
+
+    /// Accumulated time per sub-structure. All zero unless `with_timing` was enabled.
+    pub fn timing_breakdown(&self) -> TimingStats {
+        self.timing.unwrap_or_default()
+    }
+
+    /// Coverage count after each `add_hash` call, oldest first.
+    ///
+    /// Empty unless recording was enabled with `with_history`.
+    pub fn coverage_history(&self) -> &[usize] {
+        &self.history
+    }
+
+    /// Per-step `(total_unique, live_components, monotone_clusters)` columns,
+    /// aligned with `coverage_history`.
+    ///
+    /// Empty unless recording was enabled with `HistoryMode::Full`.
+    pub fn history_points(&self) -> &[HistoryPoint] {
+        &self.history_points
+    }
+
+    /// Every insertion since the last `reset`, oldest first.
+    ///
+    /// Empty unless logging was enabled with `with_event_log`.
+    pub fn event_log(&self) -> &[AddEvent] {
+        self.events.as_deref().unwrap_or(&[])
+    }
+
+    /// Offsets into `coverage_history` where each episode after the first begins.
+    pub fn episode_starts(&self) -> &[usize] {
+        &self.episode_starts
+    }
+
+    /// Exchange the entire state of two trackers with the same radius.
+    ///
+    /// Lets a tracker built off to the side replace a live one in a single
+    /// step, so readers never observe a partially rebuilt tracker.
+    pub fn swap(&mut self, other: &mut Self) -> Result<(), GamecovError> {
+        if self.radius != other.radius {
+            return Err(GamecovError::RadiusMismatch {
+                left: self.radius,
+                right: other.radius,
+            });
+        }
+        std::mem::swap(self, other);
+        Ok(())
+    }
+
+    /// Independent copy of the whole tracker, history and counters included.
+    ///
+    /// Explore an episode on the fork and drop it afterwards; unlike `reset`,
+    /// the parent keeps its coverage and is never touched by the fork.
+    pub fn fork(&self) -> Self
+    where
+        D: Clone,
+    {
+        self.clone()
+    }
+
+    pub fn reset(&mut self) {
+        self.reset_keep_history();
+        self.history.clear();
+        self.history_points.clear();
+        if let Some(events) = self.events.as_mut() {
+            events.clear();
+        }
+        self.recent_new.clear();
+        self.episode_starts.clear();
+        self.peak_coverage = 0;
+        self.next_sequence = 0;
+        if let Some(budget) = self.budget.as_mut() {
+            budget.evicted = 0;
+        }
+        if let Some(stats) = self.timing.as_mut() {
+            *stats = TimingStats::default();
+        }
+    }
+
+    /// Clear all coverage state but keep `coverage_history`, marking the start
+    /// of a new episode so per-episode curves can share one timeline.
+    pub fn reset_keep_history(&mut self) {
+        self.bktree.clear();
+        self.uf = UnionFindInner::with_strategy(self.uf.strategy());
+        self.exact.clear();
+        if let Some(sequence) = self.sequence.as_mut() {
+            sequence.clear();
+        }
+        if let Some(occurrences) = self.occurrences.as_mut() {
+            occurrences.clear();
+        }
+        if let Some(edges) = self.edges.as_mut() {
+            edges.clear();
+        }
+        self.edge_count = 0;
+        self.last_add_merged = false;
+        self.clusters_founded = 0;
+        if let Some(budget) = self.budget.as_mut() {
+            budget.last_seen.clear();
+        }
+        if self.history_mode != HistoryMode::Off {
+            self.episode_starts.push(self.history.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_tracker() {
+        let tracker = CoverageTrackerInner::new(5);
+        assert_eq!(tracker.coverage_count(), 0);
+        assert_eq!(tracker.total_unique(), 0);
+    }
+
+    #[test]
+    fn test_single_hash() {
+        let mut tracker = CoverageTrackerInner::new(5);
+        assert!(tracker.add_hash(42));
+        assert_eq!(tracker.coverage_count(), 1);
+        assert_eq!(tracker.total_unique(), 1);
+    }
+
+    #[test]
+    fn test_exact_duplicate() {
+        let mut tracker = CoverageTrackerInner::new(5);
+        assert!(tracker.add_hash(42));
+        assert!(!tracker.add_hash(42)); // duplicate
+        assert_eq!(tracker.total_unique(), 1);
+        assert_eq!(tracker.coverage_count(), 1);
+    }
+
+    #[test]
+    fn test_radius_zero_is_pure_dedup() {
+        let mut tracker = CoverageTrackerInner::new(0);
+        assert!(tracker.add_hash(0b0000));
+        assert!(tracker.add_hash(0b0001));
+        assert!(!tracker.add_hash(0b0001));
+        assert_eq!(tracker.total_unique(), 2);
+        assert_eq!(tracker.coverage_count(), 2);
+    }
+
+    #[test]
+    fn test_nearby_hashes_merge() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        // 0b0000 and 0b0001 have Hamming distance 1 (<= radius 2)
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0001);
+        assert_eq!(tracker.total_unique(), 2);
+        assert_eq!(tracker.coverage_count(), 1); // merged into one component
+    }
+
+    #[test]
+    fn test_add_hash_neighbors() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        assert_eq!(tracker.add_hash_neighbors(0b0000), 0); // fresh cluster
+        assert_eq!(tracker.add_hash_neighbors(0b0011), 0);
+        assert_eq!(tracker.add_hash_neighbors(0b0001), 2); // adjacent to both
+        assert_eq!(tracker.add_hash_neighbors(0b0001), 0); // exact duplicate
+        assert_eq!(tracker.total_unique(), 3);
+    }
+
+    #[test]
+    fn test_add_hash_increased() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        assert!(tracker.add_hash_increased(0b0000));
+        assert!(!tracker.add_hash_increased(0b0001)); // joins the cluster
+        assert!(!tracker.add_hash_increased(0b0001)); // exact duplicate
+        assert!(tracker.add_hash_increased(0b1110));
+        assert_eq!(tracker.coverage_count(), 2);
+    }
+
+    #[test]
+    fn test_distant_hashes_separate() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        // 0b0000 and 0b0111 have Hamming distance 3 (> radius 1)
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0111);
+        assert_eq!(tracker.total_unique(), 2);
+        assert_eq!(tracker.coverage_count(), 2); // separate components
+    }
+
+    #[test]
+    fn test_bridging_reduces_components() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        // A: 0b0000, B: 0b0011 (distance 2 from A, separate)
+        // C: 0b0001 (distance 1 from A, distance 1 from B -> bridges them)
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        assert_eq!(tracker.coverage_count(), 2);
+
+        tracker.add_hash(0b0001); // bridges A and B
+        assert_eq!(tracker.coverage_count(), 1);
+    }
+
+    #[test]
+    fn test_would_bridge() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        assert!(tracker.would_bridge(0b0001)); // adjacent to both clusters
+        assert!(!tracker.would_bridge(0b1000)); // adjacent to one cluster only
+        assert!(!tracker.would_bridge(0b0000)); // exact duplicate
+        assert_eq!(tracker.coverage_count(), 2); // query did not mutate
+
+        tracker.add_hash(0b0001);
+        assert!(!tracker.would_bridge(0b0010)); // both neighbours now share a cluster
     }
 
     #[test]
@@ -128,4 +1696,1016 @@ mod tests {
         assert_eq!(tracker.coverage_count(), 0);
         assert_eq!(tracker.total_unique(), 0);
     }
+
+    #[test]
+    fn test_debug_check_invariants() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        for x in [0b0000, 0b0001, 0b0111, 0b1111, 0b0001] {
+            tracker.add_hash(x);
+            assert!(tracker.debug_check_invariants().is_ok());
+        }
+        tracker.exact.insert(42); // bypass the insertion path
+        assert!(tracker.debug_check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_rebuild_tree_keeps_coverage() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        let mut rng = crate::rng::SplitMix64::new(3);
+        let mut x = 0u64;
+        for _ in 0..2_000 {
+            x ^= 1 << rng.below(64); // slow drift, one bit per frame
+            tracker.add_hash(x);
+        }
+        let (coverage, unique) = (tracker.coverage_count(), tracker.total_unique());
+        let depth = tracker.bktree.stats().mean_depth;
+        let before = tracker.bktree.find_all_within(999, 2).len();
+
+        tracker.rebuild_tree();
+        assert_eq!(tracker.coverage_count(), coverage);
+        assert_eq!(tracker.total_unique(), unique);
+        assert!(tracker.bktree.stats().mean_depth < depth);
+        assert_eq!(tracker.bktree.find_all_within(999, 2).len(), before);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_nearest_clusters() {
+        let mut tracker = CoverageTrackerInner::new(1).with_deterministic(true);
+        for x in [0b0000_0000, 0b0000_0001, 0b0000_0011, 0b1111_0000, 0b1111_1000] {
+            tracker.add_hash(x);
+        }
+        tracker.add_hash(u64::MAX);
+        assert_eq!(
+            tracker.nearest_clusters(0b0000_0111, 2),
+            vec![(0b0000_0000, 1), (0b1111_0000, 7)]
+        );
+        assert_eq!(tracker.nearest_clusters(0, 5).len(), 3);
+        assert_eq!(tracker.total_unique(), 6); // the query stored nothing
+    }
+
+    #[test]
+    fn test_debug_state() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b00, 0b01, 0b01, 0b1100] {
+            tracker.add_hash(x);
+        }
+        let expected = TrackerDebugState {
+            exact_len: 3,
+            tree_len: 3,
+            uf_len: 3,
+            uf_components: 2,
+        };
+        assert_eq!(tracker.debug_state(), expected);
+        tracker.exact.insert(42); // bypass the insertion path
+        assert_eq!(tracker.debug_state().exact_len, 4);
+        assert_eq!(tracker.debug_state().tree_len, 3);
+    }
+
+    #[test]
+    fn test_masked_metric_clusters_ignoring_mask() {
+        use crate::distance::MaskedHamming;
+
+        // low byte is a timestamp and must not separate frames
+        let mut tracker = CoverageTrackerInner::with_metric(0, MaskedHamming { mask: 0xFF });
+        assert!(tracker.add_hash(0x1_00));
+        assert!(tracker.add_hash(0x1_FF)); // distinct hash, same masked content
+        assert!(tracker.add_hash(0x3_00));
+        assert_eq!(tracker.total_unique(), 3);
+        assert_eq!(tracker.coverage_count(), 2);
+    }
+
+    #[test]
+    fn test_weighted_tracker_discounts_noise_bits() {
+        // the high byte is meaningful, every other bit is noise
+        let mut weights = [1; 64];
+        weights[56..].fill(8);
+        let mut tracker = CoverageTrackerInner::with_weights(4, weights);
+        tracker.add_hash(0);
+        tracker.add_hash(0b1111); // four noise bits: distance 4
+        tracker.add_hash(1 << 56); // one meaningful bit: distance 8
+        assert_eq!(tracker.coverage_count(), 2);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_coverage_entropy() {
+        let mut tracker = CoverageTrackerInner::new(0);
+        assert_eq!(tracker.coverage_entropy(), 0.0);
+        for x in [1, 2, 4, 8] {
+            tracker.add_hash(x);
+        }
+        // four singleton clusters: log2(4)
+        assert!((tracker.coverage_entropy() - 2.0).abs() < 1e-12);
+
+        let mut merged = CoverageTrackerInner::new(64);
+        for x in [1, 2, 4, 8] {
+            merged.add_hash(x);
+        }
+        assert_eq!(merged.coverage_entropy(), 0.0);
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        tracker.add_hash(0b0001); // bridges the two clusters
+        tracker.add_hash(0b1111_0000);
+        assert_eq!(tracker.largest_cluster(), 3);
+        assert_eq!(tracker.singletons(), 1);
+        assert_eq!(tracker.peak_coverage(), 2);
+        assert_eq!(
+            tracker.summary_json(),
+            r#"{"radius":1,"total_unique":4,"coverage_count":2,"largest_cluster":3,"singletons":1,"peak_coverage":2}"#
+        );
+    }
+
+    #[test]
+    fn test_timing_breakdown() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        tracker.add_hash(1);
+        assert_eq!(tracker.timing_breakdown(), TimingStats::default());
+
+        let mut tracker = CoverageTrackerInner::new(2).with_timing(true);
+        for x in 0..100 {
+            tracker.add_hash(x);
+        }
+        tracker.add_hash(0); // duplicates are not timed
+        let stats = tracker.timing_breakdown();
+        assert_eq!(stats.samples, 100);
+        assert!(stats.query > Duration::ZERO);
+
+        tracker.reset();
+        assert_eq!(tracker.timing_breakdown(), TimingStats::default());
+    }
+
+    #[test]
+    fn test_sequence_ids() {
+        let mut tracker = CoverageTrackerInner::new(1).with_sequence_ids(true);
+        tracker.add_hash(10);
+        tracker.add_hash(20);
+        tracker.add_hash(10); // duplicates do not consume an id
+        tracker.add_hash(30);
+        assert_eq!(tracker.sequence_of(10), Some(0));
+        assert_eq!(tracker.sequence_of(30), Some(2));
+        assert_eq!(tracker.sequence_of(99), None);
+
+        tracker.reset_keep_history();
+        tracker.add_hash(40);
+        assert_eq!(tracker.sequence_of(10), None);
+        assert_eq!(tracker.sequence_of(40), Some(3)); // counter keeps running
+
+        let mut plain = CoverageTrackerInner::new(1);
+        plain.add_hash(10);
+        assert_eq!(plain.sequence_of(10), None);
+    }
+
+    #[test]
+    fn test_increase_radius_matches_fresh_build() {
+        let hashes: Vec<u64> = (0..300u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 52)
+            .collect();
+        let mut tracker = CoverageTrackerInner::new(1);
+        for &x in &hashes {
+            tracker.add_hash(x);
+        }
+        tracker.increase_radius(3).unwrap();
+        assert_eq!(tracker.radius(), 3);
+
+        let mut fresh = CoverageTrackerInner::new(3);
+        for &x in &hashes {
+            fresh.add_hash(x);
+        }
+        assert_eq!(tracker.coverage_count(), fresh.coverage_count());
+        assert!(tracker.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_increase_radius_records_new_edges_once() {
+        let mut tracker = CoverageTrackerInner::new(0).with_edges(true);
+        tracker.add_hash(0b00);
+        tracker.add_hash(0b11);
+        tracker.increase_radius(2).unwrap();
+        assert_eq!(tracker.edges(), &[(0b11, 0b00)]);
+        assert_eq!(tracker.edge_count(), 1);
+        assert_eq!(tracker.coverage_count(), 1);
+    }
+
+    #[test]
+    fn test_edge_count_is_monotone_and_matches_recorded_edges() {
+        let mut tracker = CoverageTrackerInner::new(1).with_edges(true);
+        let mut last = 0;
+        for x in [0b0000, 0b0011, 0b0001, 0b0001, 0b0010, 0b1111_0000] {
+            tracker.add_hash(x);
+            assert!(tracker.edge_count() >= last);
+            last = tracker.edge_count();
+        }
+        // 0001-0000, 0001-0011, 0010-0000, 0010-0011
+        assert_eq!(tracker.edge_count(), 4);
+        assert_eq!(tracker.edges().len(), 4);
+        assert_eq!(tracker.coverage_count(), 2);
+
+        let adopted = CoverageTrackerInner::from_hashes(&tracker.export_hashes(), 1);
+        assert_eq!(adopted.edge_count(), 4);
+        tracker.reset();
+        assert_eq!(tracker.edge_count(), 0);
+    }
+
+    #[test]
+    fn test_increase_radius_rejects_decrease() {
+        let mut tracker = CoverageTrackerInner::new(3);
+        assert_eq!(
+            tracker.increase_radius(2),
+            Err(GamecovError::RadiusDecrease {
+                current: 3,
+                requested: 2
+            })
+        );
+        assert_eq!(tracker.radius(), 3);
+    }
+
+    #[test]
+    fn test_add_hash_cluster_returns_root() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        let a = tracker.add_hash_cluster(0b0000);
+        assert_eq!(a, 0b0000);
+        let b = tracker.add_hash_cluster(0b1100);
+        assert_ne!(a, b);
+        // 0b0100 bridges both clusters, so its root is shared by all three
+        let c = tracker.add_hash_cluster(0b0100);
+        assert_eq!(tracker.add_hash_cluster(0b0000), c); // duplicate
+        assert_eq!(tracker.add_hash_cluster(0b1100), c);
+        assert_eq!(tracker.total_unique(), 3);
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut live = CoverageTrackerInner::new(2);
+        live.add_hash(1);
+        let mut staged = CoverageTrackerInner::new(2);
+        staged.add_hash(0);
+        staged.add_hash(u64::MAX);
+        live.swap(&mut staged).unwrap();
+        assert_eq!(live.total_unique(), 2);
+        assert_eq!(staged.total_unique(), 1);
+
+        let mut other_radius = CoverageTrackerInner::new(3);
+        assert!(live.swap(&mut other_radius).is_err());
+        assert_eq!(live.total_unique(), 2);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let report = analyze(&[0b0000, 0b0000, 0b0001, 0b0011, 0b1111_0000], 1);
+        assert_eq!(
+            report,
+            DedupReport {
+                total: 5,
+                exact_unique: 4,
+                clusters_at_radius: 2,
+                largest_cluster: 3,
+            }
+        );
+        assert_eq!(analyze(&[], 3), DedupReport::default());
+    }
+
+    #[test]
+    fn test_last_add_merged() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        tracker.add_hash(0b0000);
+        assert!(!tracker.last_add_merged());
+        tracker.add_hash(0b0001); // joins one cluster
+        assert!(tracker.last_add_merged());
+        tracker.add_hash(0b0111);
+        assert!(!tracker.last_add_merged());
+        tracker.add_hash(0xF000); // new singleton
+        assert!(!tracker.last_add_merged());
+        tracker.add_hash(0b0011); // bridges {0000, 0001} and {0111}
+        assert!(tracker.last_add_merged());
+        assert_eq!(tracker.coverage_count(), 2);
+        tracker.add_hash(0b0011);
+        assert!(!tracker.last_add_merged());
+    }
+
+    #[test]
+    fn test_add_from_iter() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        assert_eq!(tracker.add_from_iter((0..10u64).map(|i| i % 4)), 4);
+        assert_eq!(tracker.add_from_iter(std::iter::empty()), 0);
+        assert_eq!(tracker.add_from_iter([3, 100]), 1);
+        assert_eq!(tracker.total_unique(), 5);
+    }
+
+    #[test]
+    fn test_add_hashes_mask() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        tracker.add_hash(7);
+        assert_eq!(tracker.add_hashes_mask(&[1, 7, 2, 1]), vec![true, false, true, false]);
+        assert_eq!(tracker.total_unique(), 3);
+        assert!(tracker.add_hashes_mask(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_unique_hashes() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        for x in [3, 1, 3, 2] {
+            tracker.add_hash(x);
+        }
+        let mut hashes = tracker.unique_hashes_vec();
+        hashes.sort_unstable();
+        assert_eq!(hashes, vec![1, 2, 3]);
+        assert_eq!(tracker.unique_hashes().count(), tracker.total_unique());
+    }
+
+    #[test]
+    fn test_query_context() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b0000, 0b0001, 0b1100] {
+            tracker.add_hash(x);
+        }
+        let root = tracker.add_hash_cluster(0b0000);
+        let mut context = tracker.query_context(0b0100, 1);
+        context.sort_unstable();
+        assert_eq!(context, vec![(0b0000, root), (0b1100, 0b1100)]);
+        assert_eq!(tracker.total_unique(), 3); // query did not insert
+    }
+
+    #[test]
+    fn test_from_bktree_matches_add_hash() {
+        let hashes: Vec<u64> = (0..200u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 54)
+            .collect();
+        let mut tree = BKTreeInner::new();
+        let mut added = CoverageTrackerInner::new(2);
+        for &x in &hashes {
+            tree.add(x);
+            added.add_hash(x);
+        }
+        let adopted = CoverageTrackerInner::from_bktree(tree, 2);
+        assert_eq!(adopted.total_unique(), added.total_unique());
+        assert_eq!(adopted.coverage_count(), added.coverage_count());
+        assert!(adopted.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_failed_insert_leaves_state_consistent() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        for radius in [0, 1, 3] {
+            let mut tracker = CoverageTrackerInner::new(radius);
+            tracker.add_hash(0b0000);
+            tracker.add_hash(0b1111);
+
+            FAIL_BEFORE_COMMIT.with(|f| f.set(true));
+            let result = catch_unwind(AssertUnwindSafe(|| tracker.add_hash(0b0001)));
+            FAIL_BEFORE_COMMIT.with(|f| f.set(false));
+            assert!(result.is_err());
+
+            assert_eq!(tracker.total_unique(), 2);
+            assert_eq!(tracker.coverage_count(), tracker.uf.component_count());
+            assert_eq!(tracker.exact.len(), tracker.total_unique());
+            assert_eq!(tracker.debug_check_invariants(), Ok(()));
+
+            assert!(tracker.add_hash(0b0001)); // retry succeeds
+            assert_eq!(tracker.total_unique(), 3);
+            assert_eq!(tracker.debug_check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut a = CoverageTrackerInner::new(1);
+        let mut b = CoverageTrackerInner::new(1);
+        for x in [0b0000, 0b0001, 0b1100] {
+            a.add_hash(x);
+            b.add_hash(x);
+        }
+        assert!(a.diff(&mut b).is_empty());
+
+        a.add_hash(0b0100); // bridges {0000, 0001} and {1100} in a only
+        b.add_hash(0b1111_0000);
+        let diff = a.diff(&mut b);
+        assert_eq!(diff.only_in_self, vec![0b0100]);
+        assert_eq!(diff.only_in_other, vec![0b1111_0000]);
+        assert_eq!(diff.clusters_only_in_self, vec![vec![0b0000, 0b0001, 0b0100, 0b1100]]);
+        assert_eq!(
+            diff.clusters_only_in_other,
+            vec![vec![0b0000, 0b0001], vec![0b1100], vec![0b1111_0000]]
+        );
+    }
+
+    #[test]
+    fn test_cluster_size_percentiles() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        // cluster sizes: 3, 1, 1, 1
+        for x in [0b0000, 0b0001, 0b0011, 0b1111_0000, 0b1111_1111_0000, 0b1111 << 40] {
+            tracker.add_hash(x);
+        }
+        assert_eq!(
+            tracker.cluster_size_percentiles(&[0.0, 50.0, 75.0, 76.0, 100.0]),
+            Ok(vec![1, 1, 1, 3, 3])
+        );
+        assert!(tracker.cluster_size_percentiles(&[101.0]).is_err());
+        assert!(tracker.cluster_size_percentiles(&[f64::NAN]).is_err());
+        assert_eq!(
+            CoverageTrackerInner::new(1).cluster_size_percentiles(&[50.0]),
+            Ok(vec![0])
+        );
+    }
+
+    #[test]
+    fn test_extend_presorted_unique_matches_add_hash() {
+        let mut xs: Vec<u64> = (0..300u64)
+            .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 52)
+            .collect();
+        xs.sort_unstable();
+        xs.dedup();
+        for radius in [0, 1, 3] {
+            let mut bulk = CoverageTrackerInner::new(radius);
+            bulk.extend_presorted_unique(&xs);
+            let mut one_by_one = CoverageTrackerInner::new(radius);
+            for &x in &xs {
+                one_by_one.add_hash(x);
+            }
+            assert_eq!(bulk.total_unique(), one_by_one.total_unique());
+            assert_eq!(bulk.coverage_count(), one_by_one.coverage_count());
+            assert_eq!(bulk.debug_check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not strictly increasing")]
+    fn test_extend_presorted_unique_rejects_unsorted_in_debug() {
+        CoverageTrackerInner::new(1).extend_presorted_unique(&[2, 1]);
+    }
+
+    #[test]
+    fn test_novelty_score() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        assert_eq!(tracker.novelty_score(0), 1.0); // empty: maximally novel
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0001);
+        // stored member of a 2-cluster: distance 0, cluster term 1/3
+        assert!((tracker.novelty_score(0b0000) - 0.5 / 3.0).abs() < 1e-12);
+        // joins that cluster from distance 1
+        assert!((tracker.novelty_score(0b0011) - (0.5 / 64.0 + 0.5 / 3.0)).abs() < 1e-12);
+        // far away, would found a new cluster
+        let far = tracker.novelty_score(u64::MAX);
+        assert!((far - (0.5 * 63.0 / 64.0 + 0.5)).abs() < 1e-12);
+        assert_eq!(tracker.total_unique(), 2);
+    }
+
+    #[test]
+    fn test_novelty_score_weighted() {
+        let mut weights = [1; 64];
+        weights[56..].fill(8); // max distance 56 + 8 * 8 = 120
+        let mut tracker = CoverageTrackerInner::with_weights(4, weights);
+        assert_eq!(tracker.novelty_score(0), 1.0);
+        tracker.add_hash(0);
+        // one meaningful bit away: distance 8 of 120, would found a new cluster
+        assert!((tracker.novelty_score(1 << 56) - (0.5 * 8.0 / 120.0 + 0.5)).abs() < 1e-12);
+        let far = tracker.novelty_score(u64::MAX);
+        assert!((far - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_export_and_from_hashes() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        for x in [9, 0b0000, 0b0011, 9, 0b1111_0000] {
+            tracker.add_hash(x);
+        }
+        let hashes = tracker.export_hashes();
+        assert_eq!(hashes, vec![0b0000, 0b0011, 9, 0b1111_0000]);
+        let restored = CoverageTrackerInner::from_hashes(&[0b1111_0000, 9, 0b0000, 0b0011, 9], 2);
+        assert_eq!(restored.export_hashes(), hashes);
+        assert_eq!(restored.coverage_count(), tracker.coverage_count());
+    }
+
+    #[test]
+    fn test_is_over_connected() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        assert!(!tracker.is_over_connected());
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b1111_0000);
+        assert_eq!(tracker.largest_cluster_fraction(), 0.5);
+        assert!(!tracker.is_over_connected()); // exactly half is not more than half
+        tracker.add_hash(0b0001);
+        assert!(tracker.is_over_connected());
+
+        let lenient = CoverageTrackerInner::new(1).with_over_connected_fraction(0.9).unwrap();
+        assert_eq!(lenient.over_connected_fraction(), 0.9);
+    }
+
+    #[test]
+    fn test_over_connected_fraction_rejects_out_of_range() {
+        for fraction in [0.0, -0.5, 1.5, f64::NAN] {
+            let err = CoverageTrackerInner::new(1)
+                .with_over_connected_fraction(fraction)
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("outside (0, 1]"));
+        }
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let mut tracker = CoverageTrackerInner::new(2);
+        for x in [0b0000, 0b0011, 0b1111_0000, 9] {
+            tracker.add_hash(x);
+        }
+        let mut buf = Vec::new();
+        tracker.save(&mut buf).unwrap();
+        let loaded = CoverageTrackerInner::load(buf.as_slice()).unwrap();
+        assert_eq!(loaded.radius(), 2);
+        assert_eq!(loaded.export_hashes(), tracker.export_hashes());
+        assert_eq!(loaded.coverage_count(), tracker.coverage_count());
+
+        let mut tree_file = Vec::new();
+        BKTreeInner::new().save(&mut tree_file).unwrap();
+        assert!(CoverageTrackerInner::load(tree_file.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_bitset_backend_matches_hashset() {
+        let stream: Vec<u64> = (0..300u64)
+            .map(|i| (i * 37) % 97)
+            .chain([1 << 40, 1 << 40 | 1])
+            .collect();
+        let mut plain = CoverageTrackerInner::new(1);
+        let mut dense = CoverageTrackerInner::new(1)
+            .with_exact_backend(ExactBackend::BitSet { universe: 64 })
+            .unwrap();
+        for &x in &stream {
+            assert_eq!(plain.add_hash(x), dense.add_hash(x));
+        }
+        assert_eq!(plain.coverage_count(), dense.coverage_count());
+        assert_eq!(plain.export_hashes(), dense.export_hashes());
+        assert!(dense.diff(&mut plain).is_empty());
+        assert!(dense.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_fork_is_independent() {
+        let mut parent = CoverageTrackerInner::new(1).with_history(true);
+        parent.add_hash(0b0000);
+        let mut child = parent.fork();
+        child.add_hash(0b0001);
+        child.add_hash(0b1111_0000);
+        assert_eq!(child.total_unique(), 3);
+        assert_eq!(child.coverage_history(), &[1, 1, 2]);
+
+        assert_eq!(parent.total_unique(), 1);
+        assert_eq!(parent.coverage_history(), &[1]);
+        assert!(parent.add_hash(0b0001)); // still new to the parent
+        assert!(parent.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_dedup_radius_drops_near_duplicates() {
+        let mut tracker = CoverageTrackerInner::new(3).with_dedup_radius(1);
+        assert!(tracker.add_hash(0b0000));
+        assert!(!tracker.add_hash(0b0001)); // within dedup_radius: dropped
+        assert!(!tracker.would_bridge(0b0001));
+        assert!(tracker.add_hash(0b0011)); // distance 2: stored, same cluster
+        assert!(tracker.add_hash(0b1111_0000_0000));
+        assert_eq!(tracker.total_unique(), 3);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert!(tracker.debug_check_invariants().is_ok());
+        assert_eq!(CoverageTrackerInner::new(3).dedup_radius(), 0);
+    }
+
+    #[test]
+    fn test_add_hash_cluster_with_dedup_radius() {
+        let mut tracker = CoverageTrackerInner::new(3).with_dedup_radius(1);
+        let root = tracker.add_hash_cluster(0b0000);
+        assert_eq!(tracker.add_hash_cluster(0b0001), root); // dropped, absorbed by 0
+        assert_eq!(tracker.total_unique(), 1);
+        let far = tracker.add_hash_cluster(0xFF00);
+        assert_ne!(far, root);
+        assert_eq!(tracker.add_hash_cluster(0xFF01), far);
+        assert!(tracker.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_coverage_count_min_size() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b0000, 0b0001, 0b0011, 0b1111_0000, 0b1111_0001, 0b111 << 40] {
+            tracker.add_hash(x);
+        }
+        assert_eq!(tracker.coverage_count_min_size(1), tracker.coverage_count());
+        assert_eq!(tracker.coverage_count_min_size(2), 2);
+        assert_eq!(tracker.coverage_count_min_size(3), 1);
+        assert_eq!(tracker.singletons(), 1);
+    }
+
+    #[test]
+    fn test_take_bktree_keeps_configuration() {
+        let mut tracker = CoverageTrackerInner::new(3)
+            .with_history(true)
+            .with_dedup_radius(1)
+            .with_deterministic(true)
+            .with_occurrence_counts(true)
+            .with_exact_backend(ExactBackend::BitSet { universe: 64 })
+            .unwrap();
+        tracker.add_hash(0);
+        tracker.add_hash(0b110);
+        let tree = tracker.take_bktree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tracker.total_unique(), 0);
+        assert!(tracker.coverage_history().is_empty());
+        assert_eq!(tracker.dedup_radius(), 1);
+        assert!(tracker.is_deterministic());
+        assert_eq!(tracker.exact.backend(), ExactBackend::BitSet { universe: 64 });
+        tracker.add_hash(0b110);
+        assert!(!tracker.add_hash(0b111)); // still deduplicated
+        assert_eq!(tracker.occurrence_count(0b110), 1);
+        assert_eq!(tracker.coverage_history(), [1, 1]);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_into_bktree_round_trip() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b0000, 0b0001, 0b1111_0000] {
+            tracker.add_hash(x);
+        }
+        let tree = tracker.into_bktree();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.find_all_within(0b0000, 0), vec![0b0000]);
+        let tracker = CoverageTrackerInner::from_bktree(tree, 1);
+        assert_eq!(tracker.coverage_count(), 2);
+    }
+
+    #[test]
+    fn test_occurrence_counts() {
+        let mut tracker = CoverageTrackerInner::new(1).with_occurrence_counts(true);
+        for x in [5, 7, 5, 9, 5, 7] {
+            tracker.add_hash(x);
+        }
+        assert_eq!(tracker.occurrence_count(5), 3);
+        assert_eq!(tracker.occurrence_count(9), 1);
+        assert_eq!(tracker.occurrence_count(42), 0);
+        assert_eq!(tracker.most_frequent(2), vec![(5, 3), (7, 2)]);
+        assert_eq!(tracker.most_frequent(10).len(), 3);
+
+        tracker.reset();
+        assert_eq!(tracker.occurrence_count(5), 0);
+        let mut plain = CoverageTrackerInner::new(1);
+        plain.add_hash(5);
+        assert_eq!(plain.occurrence_count(5), 0);
+        assert!(plain.most_frequent(3).is_empty());
+    }
+
+    #[test]
+    fn test_with_deterministic_on_populated_tracker() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b0110, 0b0100, 0b0101, 0xF000] {
+            tracker.add_hash(x);
+        }
+        let mut tracker = tracker.with_deterministic(true);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert_eq!(tracker.uf.find(0b0110).unwrap(), 0b0100);
+        tracker.add_hash(0b0111); // neighbours are still registered
+        tracker.add_hash(0xF001);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert_eq!(tracker.uf.find(0xF001).unwrap(), 0xF000);
+        tracker.debug_check_invariants().unwrap();
+        let mut tracker = tracker.with_deterministic(false);
+        tracker.add_hash(0b1111);
+        assert_eq!(tracker.coverage_count(), 2);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_deterministic_mode_is_reproducible() {
+        let stream: Vec<u64> = (0..300u64).map(|i| (i * 2_654_435_761) % 4096).collect();
+        let build = || {
+            let mut tracker = CoverageTrackerInner::new(2).with_deterministic(true).with_edges(true);
+            for &x in &stream {
+                tracker.add_hash(x);
+            }
+            tracker.increase_radius(3).unwrap();
+            tracker
+        };
+        // separate trackers get separately seeded HashMaps
+        let (mut a, mut b) = (build(), build());
+        assert_eq!(a.edges(), b.edges());
+        for &x in &stream {
+            assert_eq!(a.add_hash_cluster(x), b.add_hash_cluster(x));
+        }
+        for root in a.roots().collect::<Vec<_>>() {
+            assert_eq!(a.component_iter(root).unwrap().min(), Some(root));
+        }
+        a.reset();
+        assert!(a.is_deterministic());
+        a.add_hash(9);
+        a.add_hash(8);
+        assert_eq!(a.add_hash_cluster(9), 8);
+    }
+
+    #[test]
+    fn test_coverage_count_at_matches_fresh_trackers() {
+        let stream: Vec<u64> = (0..200u64).map(|i| (i * 2_654_435_761) % 4096).collect();
+        let mut tracker = CoverageTrackerInner::new(2);
+        for &x in &stream {
+            tracker.add_hash(x);
+        }
+        let before = tracker.coverage_count();
+        for radius in [0, 1, 2, 3, 5] {
+            let mut fresh = CoverageTrackerInner::new(radius);
+            for &x in &stream {
+                fresh.add_hash(x);
+            }
+            assert_eq!(
+                tracker.coverage_count_at(radius),
+                fresh.coverage_count(),
+                "radius {radius}"
+            );
+        }
+        assert_eq!(tracker.coverage_count(), before);
+        assert!(tracker.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_union_counters() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for x in [0b0000, 0b0011, 0b0001, 0b0010] {
+            tracker.add_hash(x);
+        }
+        // 0001 is unioned with the roots of {0000} and {0011}, 0010 with the
+        // single root they then share; insertion dedups roots, so each merges
+        assert_eq!(tracker.union_calls(), 3);
+        assert_eq!(tracker.union_merges(), 3);
+        tracker.reset();
+        assert_eq!((tracker.union_calls(), tracker.union_merges()), (0, 0));
+    }
+
+    #[test]
+    fn test_edge_recording() {
+        let mut tracker = CoverageTrackerInner::new(1).with_edges(true);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        tracker.add_hash(0b0001);
+        let mut edges = tracker.edges().to_vec();
+        edges.sort();
+        assert_eq!(edges, vec![(0b0001, 0b0000), (0b0001, 0b0011)]);
+
+        tracker.reset();
+        assert!(tracker.edges().is_empty());
+
+        let mut plain = CoverageTrackerInner::new(1);
+        plain.add_hash(0);
+        plain.add_hash(1);
+        assert!(plain.edges().is_empty());
+    }
+
+    #[test]
+    fn test_with_capacity_then_shrink_to_fit() {
+        let mut tracker = CoverageTrackerInner::with_capacity(1, 10_000);
+        let reserved = tracker.memory_bytes();
+        assert!(reserved > CoverageTrackerInner::new(1).memory_bytes() + 10_000 * 8);
+        for x in 0..100u64 {
+            tracker.add_hash(x.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        tracker.shrink_to_fit();
+        assert!(tracker.memory_bytes() < reserved / 10);
+        assert_eq!(tracker.coverage_count(), 100);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_memory_budget_evicts_cold_clusters() {
+        // chains of 4 hashes one bit apart, scattered far from each other
+        let cluster = |i: u64| {
+            let base = i.wrapping_mul(0x9E37_79B9_7F4A_7C15) & !0xF;
+            (0..4).map(move |b| base | ((1 << b) - 1))
+        };
+        let mut tracker = CoverageTrackerInner::new(1).with_edges(true);
+        for x in (0..20).flat_map(cluster) {
+            tracker.add_hash(x);
+        }
+        let budget = 2 * tracker.memory_bytes();
+        tracker.set_memory_budget(budget);
+        assert_eq!(tracker.evicted_count(), 0);
+
+        for i in 20..200 {
+            for x in cluster(i) {
+                tracker.add_hash(x);
+            }
+            tracker.add_hash(cluster(0).next().unwrap()); // keep cluster 0 warm
+            assert!(tracker.memory_bytes() <= budget, "over budget after cluster {i}");
+        }
+        assert!(tracker.evicted_count() > 0);
+        assert_eq!(tracker.total_unique() + tracker.evicted_count(), 200 * 4);
+        assert!(tracker.exact.contains(&cluster(0).next().unwrap()));
+        assert!(!tracker.exact.contains(&cluster(1).next().unwrap()));
+        assert!(cluster(199).all(|x| tracker.exact.contains(&x)));
+        assert_eq!(tracker.coverage_count() * 4, tracker.total_unique());
+        assert!(tracker
+            .edges()
+            .iter()
+            .all(|&(a, b)| tracker.exact.contains(&a) && tracker.exact.contains(&b)));
+        tracker.debug_check_invariants().unwrap();
+
+        // an evicted region counts as new again
+        assert!(tracker.add_hash(cluster(1).next().unwrap()));
+    }
+
+    #[test]
+    fn test_manual_union() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        tracker.add_hash(0);
+        tracker.add_hash(u64::MAX);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert!(tracker.manual_union(0, u64::MAX));
+        assert_eq!(tracker.coverage_count(), 1);
+        assert!(!tracker.manual_union(u64::MAX, 0));
+
+        // absent hashes are stored without linking to their neighbours
+        assert!(tracker.manual_union(0xF0, 0xF000));
+        assert_eq!(tracker.total_unique(), 4);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert!(tracker.manual_union(1, 0xF001)); // not linked to 0 or 0xF000
+        assert_eq!(tracker.total_unique(), 6);
+        assert_eq!(tracker.coverage_count(), 3);
+        tracker.add_hash(0xF003); // within radius of 0xF001
+        tracker.add_hash(0xF002); // within radius of 0xF000 and 0xF003: bridges
+        assert_eq!(tracker.coverage_count(), 2);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_memory_budget_keeps_cluster_fed_by_soft_duplicates() {
+        let mut tracker = CoverageTrackerInner::new(3).with_dedup_radius(1);
+        tracker.add_hash(0);
+        let mut rng = crate::rng::SplitMix64::new(3);
+        for _ in 0..2_000 {
+            tracker.add_hash(rng.next_u64());
+        }
+        tracker.set_memory_budget(2 * tracker.memory_bytes());
+        for _ in 0..4_000 {
+            for bit in 0..4 {
+                assert!(!tracker.add_hash(1 << bit)); // absorbed by 0
+            }
+            tracker.add_hash(rng.next_u64());
+        }
+        assert!(tracker.evicted_count() > 0);
+        assert!(tracker.exact.contains(&0));
+        assert_eq!(tracker.add_hash_cluster(1), tracker.uf.find(0).unwrap());
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_manual_union_under_memory_budget() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        for i in 0..200u64 {
+            tracker.add_hash(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        // so small that only the hottest cluster ever survives
+        tracker.set_memory_budget(1);
+        let (a, b) = (0xAAAA_0000_0000_0000, 0x5555_0000_0000_0000);
+        assert!(tracker.manual_union(a, b));
+        assert!(tracker.exact.contains(&a) && tracker.exact.contains(&b));
+        assert_eq!(tracker.uf.find(a).unwrap(), tracker.uf.find(b).unwrap());
+        assert_eq!(tracker.total_unique(), 2);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_manual_union_keeps_statistics() {
+        let mut tracker = CoverageTrackerInner::new(3)
+            .with_history(true)
+            .with_edges(true)
+            .with_event_log(true)
+            .with_dedup_radius(1);
+        tracker.add_hash(0);
+        assert!(tracker.manual_union(0b110, 0xF000)); // both stored as singletons
+        assert_eq!(tracker.coverage_history(), [1, 2, 3]);
+        assert_eq!(tracker.coverage_count(), 2);
+        assert_eq!(tracker.event_log().len(), 3);
+        assert_eq!(tracker.edges(), [(0b110, 0xF000)]);
+        assert_eq!(tracker.edge_count(), 1);
+        assert_eq!(tracker.recent_new_clusters(10), 3);
+
+        // 0xF001 is a soft duplicate of 0xF000 and stands for it
+        assert!(tracker.manual_union(0xF001, 0));
+        assert!(!tracker.exact.contains(&0xF001));
+        assert_eq!(tracker.coverage_count(), 1);
+        tracker.debug_check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_add_hashes_events() {
+        let mut tracker = CoverageTrackerInner::new(1);
+        let events = tracker.add_hashes_events(&[0b00, 0b11, 0b01, 0b01]);
+        let summary: Vec<(bool, usize, bool, usize)> = events
+            .iter()
+            .map(|e| (e.was_new, e.neighbor_count, e.merged, e.components_after))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (true, 0, false, 1),
+                (true, 0, false, 2),
+                (true, 2, true, 1),
+                (false, 0, false, 1)
+            ]
+        );
+        assert_eq!(events[2].hash, 0b01);
+    }
+
+    #[test]
+    fn test_event_log() {
+        let mut tracker = CoverageTrackerInner::new(1).with_event_log(true);
+        for x in [0b00, 0b00, 0b11, 0b01] {
+            tracker.add_hash(x);
+        }
+        let log: Vec<(u64, bool, usize)> = tracker
+            .event_log()
+            .iter()
+            .map(|e| (e.hash, e.was_new, e.components_after))
+            .collect();
+        assert_eq!(
+            log,
+            [(0b00, true, 1), (0b00, false, 1), (0b11, true, 2), (0b01, true, 1)]
+        );
+
+        tracker.reset_keep_history();
+        tracker.add_hash(7);
+        assert_eq!(tracker.event_log().len(), 5);
+        tracker.reset();
+        assert!(tracker.event_log().is_empty());
+        assert!(CoverageTrackerInner::new(1).event_log().is_empty());
+    }
+
+    #[test]
+    fn test_history_recording() {
+        let mut tracker = CoverageTrackerInner::new(1).with_history(true);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        tracker.add_hash(0b0011); // duplicates are recorded too
+        tracker.add_hash(0b0001);
+        assert_eq!(tracker.coverage_history(), &[1, 2, 2, 1]);
+
+        tracker.reset();
+        assert!(tracker.coverage_history().is_empty());
+        assert!(CoverageTrackerInner::new(1).coverage_history().is_empty());
+    }
+
+    #[test]
+    fn test_full_history_columns() {
+        let mut tracker = CoverageTrackerInner::new(1).with_history_mode(HistoryMode::Full);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0111);
+        tracker.add_hash(0b0001);
+        tracker.add_hash(0b0001);
+        tracker.add_hash(0b0011); // bridges both clusters
+        let live: Vec<usize> = tracker.history_points().iter().map(|p| p.live_components).collect();
+        let monotone: Vec<usize> = tracker.history_points().iter().map(|p| p.monotone_clusters).collect();
+        let unique: Vec<usize> = tracker.history_points().iter().map(|p| p.total_unique).collect();
+        assert_eq!(live, vec![1, 2, 2, 2, 1]);
+        assert_eq!(monotone, vec![1, 2, 2, 2, 2]);
+        assert_eq!(unique, vec![1, 2, 3, 3, 4]);
+        assert_eq!(tracker.coverage_history(), live.as_slice());
+
+        let live_only = CoverageTrackerInner::new(1).with_history_mode(HistoryMode::Live);
+        assert!(live_only.history_points().is_empty());
+        assert_eq!("full".parse::<HistoryMode>(), Ok(HistoryMode::Full));
+        assert!("all".parse::<HistoryMode>().is_err());
+    }
+
+    #[test]
+    fn test_recent_new_clusters() {
+        let mut tracker = CoverageTrackerInner::new(1).with_recent_window(4);
+        tracker.add_hash(0);
+        tracker.add_hash(0xFF00);
+        assert_eq!(tracker.recent_new_clusters(10), 2);
+        tracker.add_hash(1); // joins 0
+        tracker.add_hash(1); // exact duplicate
+        tracker.add_hash(3); // joins 1
+        assert_eq!(tracker.recent_new_clusters(3), 0);
+        assert_eq!(tracker.recent_new_clusters(4), 1);
+        assert_eq!(tracker.recent_new_clusters(100), 1, "older inserts fall out");
+        tracker.reset();
+        assert_eq!(tracker.recent_new_clusters(100), 0);
+        let mut off = CoverageTrackerInner::new(1).with_recent_window(0);
+        off.add_hash(0);
+        assert_eq!(off.recent_new_clusters(1), 0);
+    }
+
+    #[test]
+    fn test_reset_keep_history() {
+        let mut tracker = CoverageTrackerInner::new(5).with_history(true);
+        tracker.add_hash(1);
+        tracker.add_hash(u64::MAX);
+        tracker.reset_keep_history();
+        assert_eq!(tracker.total_unique(), 0);
+        assert_eq!(tracker.coverage_count(), 0);
+
+        tracker.add_hash(1);
+        assert_eq!(tracker.coverage_history(), &[1, 2, 1]);
+        assert_eq!(tracker.episode_starts(), &[2]);
+    }
 }
@@ -0,0 +1,164 @@
+use std::io::{Read, Write};
+
+use crate::error::GamecovError;
+
+/// Version of the binary layout written by `save`. Bump on any change to it;
+/// `load` rejects files written with a different version.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"GCOV";
+const HEADER_LEN: usize = 4 + 1 + 4 + 8;
+
+/// What a saved file holds, so one kind is never loaded as another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Kind {
+    BKTree = 1,
+    Tracker = 2,
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE 802.3, as used by zlib and PNG) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| {
+        CRC_TABLE[((c ^ u32::from(b)) & 0xFF) as usize] ^ (c >> 8)
+    })
+}
+
+/// Write `payload` framed as: magic, kind, version, payload length, payload, CRC-32.
+///
+/// All integers are little-endian.
+pub(crate) fn write_frame<W: Write>(mut w: W, kind: Kind, payload: &[u8]) -> std::io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&[kind as u8])?;
+    w.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    w.write_all(&(payload.len() as u64).to_le_bytes())?;
+    w.write_all(payload)?;
+    w.write_all(&crc32(payload).to_le_bytes())
+}
+
+/// Read a frame written by `write_frame`, validating every header field and
+/// the checksum, and return its payload.
+pub(crate) fn read_frame<R: Read>(mut r: R, kind: Kind) -> Result<Vec<u8>, GamecovError> {
+    let mut buf = Vec::new();
+    r.read_to_end(&mut buf)
+        .map_err(|e| GamecovError::Format(format!("read failed: {e}")))?;
+    if buf.len() < HEADER_LEN || &buf[..4] != MAGIC {
+        return Err(GamecovError::Format("not a gamecov file (bad magic)".to_string()));
+    }
+    if buf[4] != kind as u8 {
+        return Err(GamecovError::Format(format!(
+            "expected a {kind:?} file but found kind {}",
+            buf[4]
+        )));
+    }
+    let version = u32::from_le_bytes(buf[5..9].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(GamecovError::Format(format!(
+            "format version {version} is not supported (this build reads version {FORMAT_VERSION})"
+        )));
+    }
+    let len = u64::from_le_bytes(buf[9..HEADER_LEN].try_into().unwrap());
+    let body = &buf[HEADER_LEN..];
+    if (body.len() as u64) != len.saturating_add(4) {
+        return Err(GamecovError::Format(format!(
+            "truncated or oversized file: header says {len} payload bytes"
+        )));
+    }
+    let (payload, crc) = body.split_at(body.len() - 4);
+    if crc32(payload) != u32::from_le_bytes(crc.try_into().unwrap()) {
+        return Err(GamecovError::Format("checksum mismatch: file is corrupted".to_string()));
+    }
+    Ok(payload.to_vec())
+}
+
+/// Encode `count` followed by each value, little-endian.
+pub(crate) fn put_u64s(out: &mut Vec<u8>, values: impl ExactSizeIterator<Item = u64>) {
+    out.reserve(8 * (values.len() + 1));
+    out.extend_from_slice(&(values.len() as u64).to_le_bytes());
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Decode a sequence written by `put_u64s`, requiring it to end the payload.
+pub(crate) fn get_u64s(bytes: &[u8]) -> Result<Vec<u64>, GamecovError> {
+    let malformed = || GamecovError::Format("malformed payload: bad value count".to_string());
+    let (count, rest) = bytes.split_first_chunk::<8>().ok_or_else(malformed)?;
+    if (rest.len() as u64) != u64::from_le_bytes(*count).saturating_mul(8) {
+        return Err(malformed());
+    }
+    Ok(rest
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_frame_round_trip_and_rejections() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, Kind::BKTree, b"payload").unwrap();
+        assert_eq!(read_frame(buf.as_slice(), Kind::BKTree).unwrap(), b"payload");
+
+        assert!(read_frame(buf.as_slice(), Kind::Tracker)
+            .unwrap_err()
+            .to_string()
+            .contains("expected a Tracker"));
+        assert!(read_frame(&buf[..buf.len() - 1], Kind::BKTree)
+            .unwrap_err()
+            .to_string()
+            .contains("truncated"));
+        assert!(read_frame(&b"nope"[..], Kind::BKTree)
+            .unwrap_err()
+            .to_string()
+            .contains("bad magic"));
+
+        let mut corrupt = buf.clone();
+        corrupt[HEADER_LEN] ^= 1;
+        assert!(read_frame(corrupt.as_slice(), Kind::BKTree)
+            .unwrap_err()
+            .to_string()
+            .contains("checksum"));
+
+        let mut future = buf;
+        future[5..9].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(read_frame(future.as_slice(), Kind::BKTree)
+            .unwrap_err()
+            .to_string()
+            .contains("not supported"));
+    }
+
+    #[test]
+    fn test_u64s_round_trip() {
+        let mut out = Vec::new();
+        put_u64s(&mut out, [1u64, u64::MAX].into_iter());
+        assert_eq!(get_u64s(&out).unwrap(), vec![1, u64::MAX]);
+        assert!(get_u64s(&out[..out.len() - 1]).is_err());
+        assert!(get_u64s(&[]).is_err());
+    }
+}
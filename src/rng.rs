@@ -0,0 +1,45 @@
+/// SplitMix64: a tiny, seedable PRNG for reproducible sampling.
+///
+/// Not cryptographically secure; only used where results must be repeatable
+/// from a seed without pulling in an RNG dependency.
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n` (Lemire's multiply-shift; negligible bias for n << 2^64).
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reproducible_from_seed() {
+        let (mut a, mut b) = (SplitMix64::new(7), SplitMix64::new(7));
+        assert!((0..4).all(|_| a.next_u64() == b.next_u64()));
+        assert_ne!(SplitMix64::new(8).next_u64(), SplitMix64::new(7).next_u64());
+    }
+
+    #[test]
+    fn test_below_in_range() {
+        let mut rng = SplitMix64::new(0);
+        assert!((0..1000).all(|_| rng.below(10) < 10));
+        assert_eq!(rng.below(1), 0);
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::{Mutex, MutexGuard};
+
+use crate::monitor::CoverageTrackerInner;
+
+/// Coverage tracker split into independently locked shards.
+///
+/// Each hash is routed to a shard by its high bits, so concurrent writers only
+/// contend when they hit the same shard. A given hash always lands in the same
+/// shard, which keeps exact-duplicate detection and `total_unique` correct.
+///
+/// Clustering is per shard: two hashes within `radius` of each other that fall
+/// in different shards are never unioned. `coverage_count` is therefore the sum
+/// of per-shard component counts, an upper bound on the unsharded count.
+pub struct ShardedCoverageTrackerInner {
+    shards: Vec<Mutex<CoverageTrackerInner>>,
+    shard_bits: u32,
+}
+
+impl ShardedCoverageTrackerInner {
+    /// Create a tracker with `num_shards` shards, rounded up to a power of two.
+    pub fn new(radius: u32, num_shards: usize) -> Self {
+        let n = num_shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..n).map(|_| Mutex::new(CoverageTrackerInner::new(radius))).collect(),
+            shard_bits: n.trailing_zeros(),
+        }
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard that owns `x` (taken from its top bits).
+    pub fn shard_of(&self, x: u64) -> usize {
+        if self.shard_bits == 0 {
+            0
+        } else {
+            (x >> (64 - self.shard_bits)) as usize
+        }
+    }
+
+    fn lock(&self, idx: usize) -> MutexGuard<'_, CoverageTrackerInner> {
+        self.shards[idx].lock().expect("shard lock poisoned")
+    }
+
+    /// Insert a hash. Returns true if the hash was new (not an exact duplicate).
+    pub fn add_hash(&self, x: u64) -> bool {
+        self.lock(self.shard_of(x)).add_hash(x)
+    }
+
+    /// Sum of per-shard component counts (clusters never span shards).
+    pub fn coverage_count(&self) -> usize {
+        (0..self.shards.len()).map(|i| self.lock(i).coverage_count()).sum()
+    }
+
+    pub fn total_unique(&self) -> usize {
+        (0..self.shards.len()).map(|i| self.lock(i).total_unique()).sum()
+    }
+
+    pub fn reset(&self) {
+        for i in 0..self.shards.len() {
+            self.lock(i).reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_count_rounds_up() {
+        assert_eq!(ShardedCoverageTrackerInner::new(5, 0).num_shards(), 1);
+        assert_eq!(ShardedCoverageTrackerInner::new(5, 3).num_shards(), 4);
+        assert_eq!(ShardedCoverageTrackerInner::new(5, 8).num_shards(), 8);
+    }
+
+    #[test]
+    fn test_shard_of_uses_high_bits() {
+        let tracker = ShardedCoverageTrackerInner::new(5, 4);
+        assert_eq!(tracker.shard_of(0), 0);
+        assert_eq!(tracker.shard_of(u64::MAX), 3);
+        assert_eq!(tracker.shard_of(1 << 62), 1);
+        assert_eq!(tracker.shard_of(0xFF), 0); // low bits do not matter
+    }
+
+    #[test]
+    fn test_exact_duplicate_across_calls() {
+        let tracker = ShardedCoverageTrackerInner::new(5, 4);
+        assert!(tracker.add_hash(u64::MAX));
+        assert!(!tracker.add_hash(u64::MAX));
+        assert!(tracker.add_hash(0));
+        assert_eq!(tracker.total_unique(), 2);
+        assert_eq!(tracker.coverage_count(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_ingest() {
+        let tracker = ShardedCoverageTrackerInner::new(0, 8);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                // every thread inserts the same keys; only one insert per key wins
+                s.spawn(|| {
+                    for i in 0..1000u64 {
+                        tracker.add_hash(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                    }
+                });
+            }
+        });
+        assert_eq!(tracker.total_unique(), 1000);
+        assert_eq!(tracker.coverage_count(), 1000);
+    }
+
+    #[test]
+    fn test_reset() {
+        let tracker = ShardedCoverageTrackerInner::new(5, 2);
+        tracker.add_hash(1);
+        tracker.add_hash(u64::MAX);
+        tracker.reset();
+        assert_eq!(tracker.total_unique(), 0);
+        assert_eq!(tracker.coverage_count(), 0);
+    }
+}
@@ -1,8 +1,24 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::error::GamecovError;
+
+/// How `union` picks the root when merging two sets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnionStrategy {
+    /// Union by rank. Fastest, but representatives depend on union order.
+    #[default]
+    ByRank,
+    /// The numerically smallest key always becomes the root, so every set's
+    /// representative is its minimum key regardless of union order. Ranks are
+    /// ignored; path splitting still keeps `find` amortized logarithmic.
+    DeterministicRoots,
+}
 
 /// Disjoint-set (union-find) with path compression and union by rank.
 ///
 /// Maps arbitrary u64 hash values to internal indices for flat-array storage.
+#[derive(Clone)]
 pub struct UnionFindInner {
     /// Map from external u64 key to internal index.
     key_to_idx: HashMap<u64, usize>,
@@ -10,9 +26,56 @@ pub struct UnionFindInner {
     idx_to_key: Vec<u64>,
     parent: Vec<usize>,
     rank: Vec<u8>,
+    /// Number of members of each set, valid at root indices only.
+    size: Vec<usize>,
+    count: usize,
+    strategy: UnionStrategy,
+    /// Keys passed to `remove` that are still present until the next `rebuild`.
+    removed: HashSet<u64>,
+    /// Successful `union` calls, and the subset that joined two different sets.
+    union_calls: u64,
+    union_merges: u64,
+}
+
+/// Read-only, point-in-time copy of a union-find partition.
+///
+/// Produced by [`UnionFindInner::snapshot`]. Every key maps directly to its
+/// representative, so lookups need no mutation and the snapshot is `Sync`:
+/// many threads can query it concurrently. Later unions on the source
+/// structure are not reflected.
+#[derive(Clone, Debug)]
+pub struct UnionFindSnapshot {
+    root_of: HashMap<u64, u64>,
     count: usize,
 }
 
+impl UnionFindSnapshot {
+    /// Representative of x at snapshot time, or `None` if x was not registered.
+    pub fn find(&self, x: u64) -> Option<u64> {
+        self.root_of.get(&x).copied()
+    }
+
+    /// True if both keys are registered and were in the same set.
+    pub fn connected(&self, a: u64, b: u64) -> bool {
+        match (self.find(a), self.find(b)) {
+            (Some(ra), Some(rb)) => ra == rb,
+            _ => false,
+        }
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn len(&self) -> usize {
+        self.root_of.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root_of.is_empty()
+    }
+}
+
 impl Default for UnionFindInner {
     fn default() -> Self {
         Self::new()
@@ -21,18 +84,30 @@ impl Default for UnionFindInner {
 
 impl UnionFindInner {
     pub fn new() -> Self {
+        Self::with_strategy(UnionStrategy::default())
+    }
+
+    pub fn with_strategy(strategy: UnionStrategy) -> Self {
         Self {
             key_to_idx: HashMap::new(),
             idx_to_key: Vec::new(),
             parent: Vec::new(),
             rank: Vec::new(),
+            size: Vec::new(),
             count: 0,
+            strategy,
+            removed: HashSet::new(),
+            union_calls: 0,
+            union_merges: 0,
         }
     }
 
     /// Register a new element. No-op if already present.
+    ///
+    /// Re-registering a key with a pending `remove` cancels the removal.
     pub fn make_set(&mut self, x: u64) {
         if self.key_to_idx.contains_key(&x) {
+            self.removed.remove(&x);
             return;
         }
         let idx = self.parent.len();
@@ -40,14 +115,35 @@ impl UnionFindInner {
         self.idx_to_key.push(x);
         self.parent.push(idx);
         self.rank.push(0);
+        self.size.push(1);
         self.count += 1;
     }
 
     /// Find the representative of x (with path splitting).
-    pub fn find(&mut self, x: u64) -> u64 {
-        let idx = self.key_to_idx[&x];
-        let root = self.find_idx(idx);
-        self.idx_to_key[root]
+    ///
+    /// The representative is stable between unions: path splitting only
+    /// repoints non-root nodes at ancestors in the same tree, so the root it
+    /// reaches never changes. Only a `union` touching x's set can change it.
+    ///
+    /// Fails with `KeyNotFound` if `x` was never registered with `make_set`.
+    pub fn find(&mut self, x: u64) -> Result<u64, GamecovError> {
+        let root = self.find_idx(self.index_of(x)?);
+        Ok(self.idx_to_key[root])
+    }
+
+    fn index_of(&self, x: u64) -> Result<usize, GamecovError> {
+        self.key_to_idx.get(&x).copied().ok_or(GamecovError::KeyNotFound(x))
+    }
+
+    /// Find the representative of x without compressing paths.
+    ///
+    /// Returns `None` if `x` was never registered with `make_set`.
+    pub fn root_of(&self, x: u64) -> Option<u64> {
+        let mut idx = *self.key_to_idx.get(&x)?;
+        while self.parent[idx] != idx {
+            idx = self.parent[idx];
+        }
+        Some(self.idx_to_key[idx])
     }
 
     fn find_idx(&mut self, mut idx: usize) -> usize {
@@ -60,27 +156,314 @@ impl UnionFindInner {
     }
 
     /// Union the sets containing a and b.
-    pub fn union(&mut self, a: u64, b: u64) {
-        let ia = self.key_to_idx[&a];
-        let ib = self.key_to_idx[&b];
+    ///
+    /// Fails with `KeyNotFound`, leaving every set unchanged, if either key
+    /// was never registered.
+    pub fn union(&mut self, a: u64, b: u64) -> Result<(), GamecovError> {
+        let ia = self.index_of(a)?;
+        let ib = self.index_of(b)?;
         let mut ra = self.find_idx(ia);
         let mut rb = self.find_idx(ib);
+        self.union_calls += 1;
         if ra == rb {
-            return;
+            return Ok(());
         }
-        if self.rank[ra] < self.rank[rb] {
-            std::mem::swap(&mut ra, &mut rb);
+        self.union_merges += 1;
+        match self.strategy {
+            UnionStrategy::ByRank => {
+                if self.rank[ra] < self.rank[rb] {
+                    std::mem::swap(&mut ra, &mut rb);
+                }
+                if self.rank[ra] == self.rank[rb] {
+                    self.rank[ra] += 1;
+                }
+            }
+            UnionStrategy::DeterministicRoots => {
+                if self.idx_to_key[rb] < self.idx_to_key[ra] {
+                    std::mem::swap(&mut ra, &mut rb);
+                }
+            }
         }
         self.parent[rb] = ra;
-        if self.rank[ra] == self.rank[rb] {
-            self.rank[ra] += 1;
-        }
+        self.size[ra] += self.size[rb];
         self.count -= 1;
+        Ok(())
     }
 
     pub fn component_count(&self) -> usize {
         self.count
     }
+
+    pub fn strategy(&self) -> UnionStrategy {
+        self.strategy
+    }
+
+    /// Number of `union` calls on registered keys, merging or not.
+    ///
+    /// Starts from zero for a new structure, including the one `rebuild` makes.
+    pub fn union_calls(&self) -> u64 {
+        self.union_calls
+    }
+
+    /// Number of `union` calls that joined two different sets.
+    pub fn union_merges(&self) -> u64 {
+        self.union_merges
+    }
+
+    /// Mark `x` for deletion. Returns false if it is not registered or already marked.
+    ///
+    /// Union-find cannot split a set, so deletion is lazy: `x` stays in place,
+    /// and every query keeps reporting the pre-removal partition, until
+    /// `rebuild` drops all marked keys at once.
+    pub fn remove(&mut self, x: u64) -> bool {
+        self.key_to_idx.contains_key(&x) && self.removed.insert(x)
+    }
+
+    /// True if some `remove` calls have not been applied by `rebuild` yet.
+    pub fn needs_rebuild(&self) -> bool {
+        !self.removed.is_empty()
+    }
+
+    /// Drop keys marked by `remove` and recompute the partition from `edges`.
+    ///
+    /// `edges` must be the connecting pairs among the surviving keys (a tracker
+    /// can supply its recorded similarity edges); pairs naming a removed or
+    /// unknown key are skipped. Costs O((n + m) α(n)) for n keys and m edges,
+    /// so rebuilding once every k removals amortizes to O((n + m) / k) each.
+    /// `union_calls` and `union_merges` carry over and do not count the
+    /// rebuild's own unions.
+    pub fn rebuild<I: IntoIterator<Item = (u64, u64)>>(&mut self, edges: I) {
+        let mut fresh = Self::with_strategy(self.strategy);
+        for &key in &self.idx_to_key {
+            if !self.removed.contains(&key) {
+                fresh.make_set(key);
+            }
+        }
+        for (a, b) in edges {
+            // pairs naming a removed or unknown key fail and are skipped
+            let _ = fresh.union(a, b);
+        }
+        fresh.union_calls = self.union_calls;
+        fresh.union_merges = self.union_merges;
+        *self = fresh;
+    }
+
+    /// Switch to `strategy`, recomputing every representative under it.
+    ///
+    /// The partition is unchanged; keys marked by `remove` are dropped as in
+    /// `rebuild`.
+    pub fn set_strategy(&mut self, strategy: UnionStrategy) {
+        let labeled: Vec<(u64, u64)> = self.iter_labeled().collect();
+        self.strategy = strategy;
+        self.rebuild(labeled);
+    }
+
+    /// Representative key of every set, in index order.
+    pub fn roots(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| self.idx_to_key[i])
+    }
+
+    /// Lazily yield the members of the set containing `x`, in index order.
+    ///
+    /// Scans every element once, so a full pass costs O(n α(n)) but allocates
+    /// nothing; use it to stream one cluster at a time instead of
+    /// materialising the whole partition.
+    pub fn component_iter(&mut self, x: u64) -> Result<impl Iterator<Item = u64> + '_, GamecovError> {
+        let root = self.find_idx(self.index_of(x)?);
+        Ok((0..self.parent.len()).filter_map(move |i| {
+            if self.find_idx(i) == root {
+                Some(self.idx_to_key[i])
+            } else {
+                None
+            }
+        }))
+    }
+
+    /// Number of members in the set containing x.
+    pub fn component_size(&mut self, x: u64) -> Result<usize, GamecovError> {
+        let root = self.find_idx(self.index_of(x)?);
+        Ok(self.size[root])
+    }
+
+    /// Sizes of all sets, one entry per component in arbitrary order.
+    pub fn component_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| self.size[i])
+    }
+
+    /// Reserve room for at least `additional` more keys.
+    pub fn reserve(&mut self, additional: usize) {
+        self.key_to_idx.reserve(additional);
+        self.idx_to_key.reserve_exact(additional);
+        self.parent.reserve_exact(additional);
+        self.rank.reserve_exact(additional);
+        self.size.reserve_exact(additional);
+    }
+
+    /// Release capacity beyond what the registered keys need.
+    pub fn shrink_to_fit(&mut self) {
+        self.key_to_idx.shrink_to_fit();
+        self.idx_to_key.shrink_to_fit();
+        self.parent.shrink_to_fit();
+        self.rank.shrink_to_fit();
+        self.size.shrink_to_fit();
+        self.removed.shrink_to_fit();
+    }
+
+    /// Approximate heap footprint in bytes, counting allocated capacity.
+    pub fn memory_bytes(&self) -> usize {
+        let table = |capacity: usize, entry: usize| capacity * (entry + 1);
+        table(self.key_to_idx.capacity(), std::mem::size_of::<(u64, usize)>())
+            + table(self.removed.capacity(), std::mem::size_of::<u64>())
+            + self.idx_to_key.capacity() * std::mem::size_of::<u64>()
+            + self.parent.capacity() * std::mem::size_of::<usize>()
+            + self.rank.capacity()
+            + self.size.capacity() * std::mem::size_of::<usize>()
+    }
+
+    /// Number of sets with at least `min` members.
+    pub fn components_at_least(&self, min: usize) -> usize {
+        self.component_sizes().filter(|&s| s >= min).count()
+    }
+
+    /// Map from set size to the number of sets with that size.
+    pub fn component_size_histogram(&self) -> HashMap<usize, usize> {
+        let mut hist = HashMap::new();
+        for size in self.component_sizes() {
+            *hist.entry(size).or_insert(0) += 1;
+        }
+        hist
+    }
+
+    /// Return the `k` largest sets as `(root, size)`, largest first.
+    ///
+    /// Ties are broken by ascending root key. Uses a partial sort, so cost is
+    /// O(components + k log k).
+    pub fn top_clusters(&self, k: usize) -> Vec<(u64, usize)> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let by_size = |a: &(u64, usize), b: &(u64, usize)| b.1.cmp(&a.1).then(a.0.cmp(&b.0));
+        let mut clusters: Vec<(u64, usize)> = (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| (self.idx_to_key[i], self.size[i]))
+            .collect();
+        if k < clusters.len() {
+            clusters.select_nth_unstable_by(k - 1, by_size);
+            clusters.truncate(k);
+        }
+        clusters.sort_unstable_by(by_size);
+        clusters
+    }
+
+    /// Yield every set as `(root, size)`, largest first, in the same order as
+    /// `top_clusters`.
+    ///
+    /// The heap is built in O(components) up front and each item costs
+    /// O(log components), so taking only the first page of a huge partition
+    /// avoids sorting the rest.
+    pub fn clusters_by_size(&self) -> impl Iterator<Item = (u64, usize)> {
+        let mut heap: BinaryHeap<(usize, Reverse<u64>)> = (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| (self.size[i], Reverse(self.idx_to_key[i])))
+            .collect();
+        std::iter::from_fn(move || heap.pop().map(|(size, Reverse(root))| (root, size)))
+    }
+
+    /// Return `(keys, labels)` as parallel vectors with dense labels `0..k`.
+    ///
+    /// Labels are assigned in order of first appearance, so two keys share a
+    /// label exactly when they are in the same set.
+    pub fn flat_labeling(&mut self) -> (Vec<u64>, Vec<u64>) {
+        let n = self.parent.len();
+        let mut root_label: HashMap<usize, u64> = HashMap::with_capacity(self.count);
+        let mut labels = Vec::with_capacity(n);
+        for idx in 0..n {
+            let root = self.find_idx(idx);
+            let next = root_label.len() as u64;
+            labels.push(*root_label.entry(root).or_insert(next));
+        }
+        (self.idx_to_key.clone(), labels)
+    }
+
+    /// Lazily yield `(key, representative)` for every registered key in index
+    /// order, splitting paths as it goes; nothing is collected up front.
+    pub fn iter_labeled(&mut self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.iter_labeled_from(0)
+    }
+
+    /// `iter_labeled` starting at the `start`th key, for resuming a chunked
+    /// export. Index order is registration order, stable until `rebuild`.
+    pub fn iter_labeled_from(&mut self, start: usize) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let n = self.parent.len();
+        (start.min(n)..n).map(move |i| {
+            let root = self.find_idx(i);
+            (self.idx_to_key[i], self.idx_to_key[root])
+        })
+    }
+
+    /// Fully compress all paths and return a read-only copy of the partition.
+    pub fn snapshot(&mut self) -> UnionFindSnapshot {
+        let mut root_of = HashMap::with_capacity(self.parent.len());
+        for idx in 0..self.parent.len() {
+            let root = self.find_idx(idx);
+            self.parent[idx] = root;
+            root_of.insert(self.idx_to_key[idx], self.idx_to_key[root]);
+        }
+        UnionFindSnapshot {
+            root_of,
+            count: self.count,
+        }
+    }
+
+    /// Number of registered elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Verify structural invariants in O(n). Intended for tests and debugging.
+    ///
+    /// Checks key/index maps agree, parent indices are in bounds, the component
+    /// count equals the number of roots, and ranks stay within log2(n).
+    pub fn debug_check_invariants(&self) -> Result<(), String> {
+        let n = self.parent.len();
+        if self.key_to_idx.len() != n || self.idx_to_key.len() != n || self.rank.len() != n {
+            return Err(format!(
+                "size mismatch: {} keys, {} indices, {n} parents, {} ranks",
+                self.key_to_idx.len(),
+                self.idx_to_key.len(),
+                self.rank.len()
+            ));
+        }
+        for (idx, &key) in self.idx_to_key.iter().enumerate() {
+            if self.key_to_idx.get(&key) != Some(&idx) {
+                return Err(format!("key {key} does not map back to index {idx}"));
+            }
+        }
+        if let Some(idx) = self.parent.iter().position(|&p| p >= n) {
+            return Err(format!("parent of index {idx} is out of bounds"));
+        }
+        let roots = (0..n).filter(|&i| self.parent[i] == i).count();
+        if roots != self.count {
+            return Err(format!("component count {} but {roots} roots", self.count));
+        }
+        let total: usize = self.component_sizes().sum();
+        if total != n {
+            return Err(format!("component sizes sum to {total}, expected {n}"));
+        }
+        let max_rank = usize::BITS - n.leading_zeros();
+        if let Some(idx) = self.rank.iter().position(|&r| u32::from(r) > max_rank) {
+            return Err(format!("rank {} at index {idx} exceeds log2({n})", self.rank[idx]));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -106,7 +489,7 @@ mod tests {
     fn test_find_self() {
         let mut uf = UnionFindInner::new();
         uf.make_set(42);
-        assert_eq!(uf.find(42), 42);
+        assert_eq!(uf.find(42).unwrap(), 42);
     }
 
     #[test]
@@ -117,13 +500,234 @@ mod tests {
         uf.make_set(3);
         assert_eq!(uf.component_count(), 3);
 
-        uf.union(1, 2);
+        uf.union(1, 2).unwrap();
         assert_eq!(uf.component_count(), 2);
-        assert_eq!(uf.find(1), uf.find(2));
+        assert_eq!(uf.find(1).unwrap(), uf.find(2).unwrap());
 
-        uf.union(2, 3);
+        uf.union(2, 3).unwrap();
         assert_eq!(uf.component_count(), 1);
-        assert_eq!(uf.find(1), uf.find(3));
+        assert_eq!(uf.find(1).unwrap(), uf.find(3).unwrap());
+    }
+
+    #[test]
+    fn test_root_of_matches_find() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=4 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(3, 4).unwrap();
+        uf.union(2, 4).unwrap();
+        for x in 1..=4 {
+            assert_eq!(uf.root_of(x), Some(uf.find(x).unwrap()));
+        }
+        assert_eq!(uf.root_of(99), None);
+    }
+
+    #[test]
+    fn test_deterministic_roots_ignore_order() {
+        let edges = [(7, 3), (9, 4), (3, 9), (12, 5), (5, 8)];
+        let mut uf1 = UnionFindInner::with_strategy(UnionStrategy::DeterministicRoots);
+        let mut uf2 = UnionFindInner::with_strategy(UnionStrategy::DeterministicRoots);
+        for &(a, b) in &edges {
+            uf1.make_set(a);
+            uf1.make_set(b);
+        }
+        for &(a, b) in edges.iter().rev() {
+            uf2.make_set(b);
+            uf2.make_set(a);
+        }
+        for &(a, b) in &edges {
+            uf1.union(a, b).unwrap();
+        }
+        for &(a, b) in edges.iter().rev() {
+            uf2.union(b, a).unwrap();
+        }
+        for x in [3, 4, 5, 7, 8, 9, 12] {
+            assert_eq!(uf1.find(x).unwrap(), uf2.find(x).unwrap());
+        }
+        assert_eq!(uf1.find(9).unwrap(), 3); // smallest key of {3, 4, 7, 9}
+        assert_eq!(uf1.find(12).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_component_sizes() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=5 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(2, 3).unwrap();
+        assert_eq!(uf.component_size(3).unwrap(), 3);
+        assert_eq!(uf.component_size(4).unwrap(), 1);
+        let mut sizes: Vec<usize> = uf.component_sizes().collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 1, 3]);
+    }
+
+    #[test]
+    fn test_missing_keys_are_errors() {
+        let mut uf = UnionFindInner::new();
+        uf.make_set(1);
+        assert_eq!(uf.find(2), Err(GamecovError::KeyNotFound(2)));
+        assert_eq!(uf.union(1, 2), Err(GamecovError::KeyNotFound(2)));
+        assert_eq!(uf.component_size(3), Err(GamecovError::KeyNotFound(3)));
+        assert!(uf.component_iter(3).is_err());
+        assert_eq!(uf.component_count(), 1); // failed union changed nothing
+        assert_eq!(uf.find(1), Ok(1));
+    }
+
+    #[test]
+    fn test_components_at_least() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=6 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(2, 3).unwrap();
+        uf.union(4, 5).unwrap();
+        assert_eq!(uf.components_at_least(0), 3);
+        assert_eq!(uf.components_at_least(1), 3);
+        assert_eq!(uf.components_at_least(2), 2);
+        assert_eq!(uf.components_at_least(3), 1);
+        assert_eq!(uf.components_at_least(4), 0);
+    }
+
+    #[test]
+    fn test_union_counters() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=3 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(2, 1).unwrap(); // already joined
+        uf.union(2, 3).unwrap();
+        assert!(uf.union(1, 99).is_err()); // not counted
+        assert_eq!((uf.union_calls(), uf.union_merges()), (3, 2));
+    }
+
+    #[test]
+    fn test_component_size_histogram() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=6 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(3, 4).unwrap();
+        let hist = uf.component_size_histogram();
+        assert_eq!(hist.get(&2), Some(&2));
+        assert_eq!(hist.get(&1), Some(&2));
+        assert_eq!(hist.len(), 2);
+    }
+
+    #[test]
+    fn test_top_clusters() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=7 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(1, 3).unwrap();
+        uf.union(4, 5).unwrap();
+        let top = uf.top_clusters(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0], (uf.find(1).unwrap(), 3));
+        assert_eq!(top[1], (uf.find(4).unwrap(), 2));
+        assert_eq!(uf.top_clusters(10).len(), 4);
+        assert!(uf.top_clusters(0).is_empty());
+    }
+
+    #[test]
+    fn test_clusters_by_size_matches_top_clusters() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=7 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        uf.union(1, 3).unwrap();
+        uf.union(4, 5).unwrap();
+        assert_eq!(uf.clusters_by_size().collect::<Vec<_>>(), uf.top_clusters(usize::MAX));
+        assert_eq!(uf.clusters_by_size().nth(1), Some((uf.find(4).unwrap(), 2)));
+        assert_eq!(UnionFindInner::new().clusters_by_size().next(), None);
+    }
+
+    #[test]
+    fn test_flat_labeling() {
+        let mut uf = UnionFindInner::new();
+        for x in [10, 20, 30, 40] {
+            uf.make_set(x);
+        }
+        uf.union(20, 40).unwrap();
+        let (keys, labels) = uf.flat_labeling();
+        assert_eq!(keys, vec![10, 20, 30, 40]);
+        assert_eq!(labels, vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn test_iter_labeled() {
+        let mut uf = UnionFindInner::with_strategy(UnionStrategy::DeterministicRoots);
+        for x in [10, 20, 30, 40] {
+            uf.make_set(x);
+        }
+        uf.union(40, 20).unwrap();
+        let all: Vec<(u64, u64)> = uf.iter_labeled().collect();
+        assert_eq!(all, [(10, 10), (20, 20), (30, 30), (40, 20)]);
+        assert_eq!(uf.iter_labeled_from(3).collect::<Vec<_>>(), [(40, 20)]);
+        assert_eq!(uf.iter_labeled_from(9).count(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_is_point_in_time() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=4 {
+            uf.make_set(x);
+        }
+        uf.union(1, 2).unwrap();
+        let snap = uf.snapshot();
+        uf.union(3, 4).unwrap(); // not visible in the snapshot
+
+        assert!(snap.connected(1, 2));
+        assert!(!snap.connected(3, 4));
+        assert!(!snap.connected(1, 99));
+        assert_eq!(snap.find(2), Some(uf.find(1).unwrap()));
+        assert_eq!(snap.find(99), None);
+        assert_eq!(snap.component_count(), 3);
+    }
+
+    #[test]
+    fn test_snapshot_concurrent_reads() {
+        let mut uf = UnionFindInner::new();
+        for x in 0..100 {
+            uf.make_set(x);
+            uf.union(x, x % 10).unwrap();
+        }
+        let snap = uf.snapshot();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let snap = &snap;
+                s.spawn(move || {
+                    for x in (t..100).step_by(4) {
+                        assert!(snap.connected(x, x % 10));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_debug_check_invariants() {
+        let mut uf = UnionFindInner::new();
+        for x in 0..8 {
+            uf.make_set(x);
+        }
+        for x in 1..8 {
+            uf.union(0, x).unwrap();
+        }
+        assert_eq!(uf.len(), 8);
+        assert!(uf.debug_check_invariants().is_ok());
+
+        uf.count += 1; // desync the component count
+        assert!(uf.debug_check_invariants().is_err());
     }
 
     #[test]
@@ -131,8 +735,87 @@ mod tests {
         let mut uf = UnionFindInner::new();
         uf.make_set(1);
         uf.make_set(2);
-        uf.union(1, 2);
-        uf.union(1, 2); // no-op
+        uf.union(1, 2).unwrap();
+        uf.union(1, 2).unwrap(); // no-op
         assert_eq!(uf.component_count(), 1);
     }
+
+    #[test]
+    fn test_remove_then_rebuild_matches_fresh() {
+        let edges = [(1, 2), (2, 3), (3, 4), (5, 6), (6, 7)];
+        let mut uf = UnionFindInner::new();
+        for x in 1..=8 {
+            uf.make_set(x);
+        }
+        for &(a, b) in &edges {
+            uf.union(a, b).unwrap();
+        }
+        assert_eq!(uf.component_count(), 3);
+
+        assert!(uf.remove(3)); // splits {1, 2, 3, 4}
+        assert!(uf.remove(6)); // splits {5, 6, 7}
+        assert!(!uf.remove(6));
+        assert!(!uf.remove(99));
+        assert_eq!(uf.component_count(), 3); // lazy until rebuild
+        assert!(uf.needs_rebuild());
+        uf.rebuild(edges);
+        assert!(!uf.needs_rebuild());
+
+        let survivors = [1, 2, 4, 5, 7, 8];
+        let mut fresh = UnionFindInner::new();
+        for x in survivors {
+            fresh.make_set(x);
+        }
+        for &(a, b) in &edges {
+            if survivors.contains(&a) && survivors.contains(&b) {
+                fresh.union(a, b).unwrap();
+            }
+        }
+        assert_eq!(uf.component_count(), fresh.component_count());
+        assert_eq!(uf.component_count(), 5);
+        assert_eq!(uf.len(), 6);
+        assert_eq!(uf.root_of(3), None);
+        for a in survivors {
+            for b in survivors {
+                assert_eq!(
+                    uf.find(a).unwrap() == uf.find(b).unwrap(),
+                    fresh.find(a).unwrap() == fresh.find(b).unwrap()
+                );
+            }
+        }
+        assert!(uf.debug_check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_make_set_cancels_pending_remove() {
+        let mut uf = UnionFindInner::new();
+        uf.make_set(1);
+        uf.remove(1);
+        uf.make_set(1);
+        assert!(!uf.needs_rebuild());
+        uf.rebuild([]);
+        assert_eq!(uf.len(), 1);
+    }
+
+    #[test]
+    fn test_roots_and_component_iter() {
+        let mut uf = UnionFindInner::new();
+        for x in 1..=6 {
+            uf.make_set(x);
+        }
+        uf.union(1, 4).unwrap();
+        uf.union(4, 6).unwrap();
+        uf.union(2, 3).unwrap();
+        assert_eq!(uf.roots().count(), 3);
+        let members: Vec<u64> = uf.component_iter(6).unwrap().collect();
+        assert_eq!(members, vec![1, 4, 6]);
+        let mut streamed: Vec<Vec<u64>> = uf
+            .roots()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|r| uf.component_iter(r).unwrap().collect())
+            .collect();
+        streamed.sort();
+        assert_eq!(streamed, vec![vec![1, 4, 6], vec![2, 3], vec![5]]);
+    }
 }
@@ -0,0 +1,173 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::bktree::BKTreeInner;
+use crate::unionfind::UnionFindInner;
+
+/// Coverage tracker over a sliding window of the most recent unique hashes.
+///
+/// Once more than `window` unique hashes have been added, the oldest one is
+/// evicted from the BK-tree and exact set. Union-find cannot split sets, so an
+/// eviction marks the clustering stale and it is rebuilt from the window's
+/// contents (one neighbour query per hash) the next time coverage is read.
+pub struct WindowedCoverageTrackerInner {
+    bktree: BKTreeInner,
+    uf: UnionFindInner,
+    exact: HashSet<u64>,
+    /// Unique hashes in the window, oldest first.
+    order: VecDeque<u64>,
+    radius: u32,
+    window: usize,
+    /// Set after an eviction; `uf` must be rebuilt before it is read.
+    stale: bool,
+}
+
+impl WindowedCoverageTrackerInner {
+    /// Create a tracker keeping at most `window` (at least 1) unique hashes.
+    pub fn new(radius: u32, window: usize) -> Self {
+        Self {
+            bktree: BKTreeInner::new(),
+            uf: UnionFindInner::new(),
+            exact: HashSet::new(),
+            order: VecDeque::new(),
+            radius,
+            window: window.max(1),
+            stale: false,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Insert a hash. Returns true if the hash was new to the window.
+    pub fn add_hash(&mut self, x: u64) -> bool {
+        self.add_hash_windowed(x).0
+    }
+
+    /// Insert a hash and report any eviction it caused.
+    ///
+    /// Returns `(was_new, evicted)`, where `evicted` is the oldest hash pushed
+    /// out of the window by this insertion. Exact duplicates never evict.
+    pub fn add_hash_windowed(&mut self, x: u64) -> (bool, Option<u64>) {
+        if !self.exact.insert(x) {
+            return (false, None); // exact duplicate
+        }
+
+        if !self.stale {
+            let neighbors = self.bktree.find_all_within(x, self.radius);
+            self.uf.make_set(x);
+            for nb in &neighbors {
+                self.uf.union(x, *nb).expect("window hashes are registered");
+            }
+        }
+        self.bktree.add(x);
+        self.order.push_back(x);
+
+        let evicted = if self.order.len() > self.window {
+            self.order.pop_front()
+        } else {
+            None
+        };
+        if let Some(old) = evicted {
+            self.exact.remove(&old);
+            self.bktree.remove(old);
+            if self.bktree.capacity_nodes() > 2 * self.window {
+                self.bktree.compact();
+            }
+            self.stale = true;
+        }
+        (true, evicted)
+    }
+
+    /// Number of clusters among the hashes currently in the window.
+    pub fn coverage_count(&mut self) -> usize {
+        self.refresh();
+        self.uf.component_count()
+    }
+
+    pub fn total_unique(&self) -> usize {
+        self.exact.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.bktree.clear();
+        self.uf = UnionFindInner::new();
+        self.exact.clear();
+        self.order.clear();
+        self.stale = false;
+    }
+
+    /// Rebuild the union-find from the window's contents if an eviction made it stale.
+    fn refresh(&mut self) {
+        if !self.stale {
+            return;
+        }
+        self.uf = UnionFindInner::new();
+        for &x in &self.order {
+            self.uf.make_set(x);
+        }
+        for &x in &self.order {
+            for nb in self.bktree.find_all_within(x, self.radius) {
+                self.uf.union(x, nb).expect("window hashes are registered");
+            }
+        }
+        self.stale = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction_returns_oldest() {
+        let mut tracker = WindowedCoverageTrackerInner::new(0, 2);
+        assert_eq!(tracker.add_hash_windowed(1), (true, None));
+        assert_eq!(tracker.add_hash_windowed(2), (true, None));
+        assert_eq!(tracker.add_hash_windowed(2), (false, None));
+        assert_eq!(tracker.add_hash_windowed(3), (true, Some(1)));
+        assert_eq!(tracker.total_unique(), 2);
+        assert!(tracker.add_hash(1)); // evicted hashes are new again
+    }
+
+    #[test]
+    fn test_eviction_splits_clusters() {
+        let mut tracker = WindowedCoverageTrackerInner::new(1, 3);
+        // 0b0001 bridges 0b0000 and 0b0011
+        tracker.add_hash(0b0001);
+        tracker.add_hash(0b0000);
+        tracker.add_hash(0b0011);
+        assert_eq!(tracker.coverage_count(), 1);
+
+        let (_, evicted) = tracker.add_hash_windowed(0b1111_0000);
+        assert_eq!(evicted, Some(0b0001));
+        assert_eq!(tracker.coverage_count(), 3); // bridge gone: {0000}, {0011}, {11110000}
+    }
+
+    #[test]
+    fn test_matches_fresh_tracker_over_window() {
+        use crate::monitor::CoverageTrackerInner;
+
+        let stream: Vec<u64> = (0..200u64).map(|i| (i * 37) % 64).collect();
+        let mut tracker = WindowedCoverageTrackerInner::new(2, 10);
+        for &x in &stream {
+            tracker.add_hash(x);
+        }
+        let mut fresh = CoverageTrackerInner::new(2);
+        for &x in &tracker.order {
+            fresh.add_hash(x);
+        }
+        assert_eq!(tracker.coverage_count(), fresh.coverage_count());
+        assert_eq!(tracker.total_unique(), fresh.total_unique());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut tracker = WindowedCoverageTrackerInner::new(1, 1);
+        tracker.add_hash(1);
+        tracker.add_hash(2);
+        tracker.reset();
+        assert_eq!(tracker.total_unique(), 0);
+        assert_eq!(tracker.coverage_count(), 0);
+    }
+}